@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.19;
+
+interface IERC20 {
+    function balanceOf(address account) external view returns (uint256);
+    function transfer(address to, uint256 amount) external returns (bool);
+    function approve(address spender, uint256 amount) external returns (bool);
+}
+
+interface IAaveV3Pool {
+    function flashLoanSimple(address receiver, address asset, uint256 amount, bytes calldata params, uint16 referralCode) external;
+}
+
+interface IBalancerVault {
+    function flashLoan(address recipient, address[] memory tokens, uint256[] memory amounts, bytes memory userData) external;
+}
+
+// uniswapV3 and algebra pools share the swap function, only their callbacks are named differently
+interface IUniswapV3Pool {
+    function token0() external view returns (address);
+    function swap(address recipient, bool zeroForOne, int256 amountSpecified, uint160 sqrtPriceLimitX96, bytes calldata data)
+        external
+        returns (int256 amount0, int256 amount1);
+    function flash(address recipient, uint256 amount0, uint256 amount1, bytes calldata data) external;
+}
+
+interface IUniswapV2Pair {
+    function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+    function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes calldata data) external;
+}
+
+/// @notice borrows an asset with a flash loan, buys an asset with it in a pool and sells it back in another,
+/// the loan is repaid with the proceeds and the rest is kept as profit
+/// @dev the kind of the pools is detected on the fly, uniswapV2 pairs answer `getReserves` and the
+/// rest are swapped as uniswapV3 pools(algebra pools included)
+contract Arbitrageur {
+    // kinds of flash loans, as in `FlashLoanKind` of the bot
+    uint8 constant AAVE_V3 = 0;
+    uint8 constant BALANCER = 1;
+    uint8 constant UNISWAP_V3 = 2;
+
+    // price limits of uniswapV3 swaps, TickMath.MIN_SQRT_RATIO + 1 and TickMath.MAX_SQRT_RATIO - 1
+    uint160 constant MIN_SQRT_RATIO = 4295128740;
+    uint160 constant MAX_SQRT_RATIO = 1461446703485210103287273052203988822378723970341;
+
+    struct Trade {
+        address assetLoan;
+        address assetTrade;
+        uint256 amountLoan;
+        address buyPool;
+        address sellPool;
+        uint256 buyAmountOutMinimum;
+        uint256 sellAmountOutMinimum;
+        uint256 minProfit;
+    }
+
+    address public immutable owner;
+
+    // contracts expected to call back during an arbitrage, reset once it's done
+    address private activeLender;
+    address private activeSwapPool;
+
+    // profit of the running arbitrage, returned once the loan is repaid
+    uint256 private tradeProfit;
+
+    constructor() {
+        owner = msg.sender;
+    }
+
+    modifier onlyOwner() {
+        require(msg.sender == owner, "not owner");
+        _;
+    }
+
+    /// @notice borrow `amountLoan` of `assetLoan` from `lender`, swap it for `assetTrade` in `buyPool`
+    /// and swap it back in `sellPool`, reverts if an output is below its minimum
+    /// @return profit amount of `assetLoan` left after repaying the loan
+    function arbitrage(
+        address assetLoan,
+        address assetTrade,
+        uint256 amountLoan,
+        address buyPool,
+        address sellPool,
+        uint8 lenderKind,
+        address lender,
+        uint256 buyAmountOutMinimum,
+        uint256 sellAmountOutMinimum,
+        uint256 minProfit
+    ) external onlyOwner returns (uint256 profit) {
+        require(amountLoan > 0, "zero amount");
+        require(buyPool != sellPool, "same pools");
+        require(lender != buyPool && lender != sellPool, "lender traded");
+
+        bytes memory data = abi.encode(
+            Trade(assetLoan, assetTrade, amountLoan, buyPool, sellPool, buyAmountOutMinimum, sellAmountOutMinimum, minProfit)
+        );
+        activeLender = lender;
+        if (lenderKind == AAVE_V3) {
+            IAaveV3Pool(lender).flashLoanSimple(address(this), assetLoan, amountLoan, data, 0);
+        } else if (lenderKind == BALANCER) {
+            address[] memory tokens = new address[](1);
+            uint256[] memory amounts = new uint256[](1);
+            (tokens[0], amounts[0]) = (assetLoan, amountLoan);
+            IBalancerVault(lender).flashLoan(address(this), tokens, amounts, data);
+        } else if (lenderKind == UNISWAP_V3) {
+            bool zero = IUniswapV3Pool(lender).token0() == assetLoan;
+            IUniswapV3Pool(lender).flash(address(this), zero ? amountLoan : 0, zero ? 0 : amountLoan, data);
+        } else {
+            revert("unknown lender");
+        }
+        activeLender = address(0);
+
+        profit = tradeProfit;
+        tradeProfit = 0;
+    }
+
+    /// @notice aaveV3 flash loan callback, the pool pulls the loan and its premium
+    function executeOperation(address asset, uint256 amount, uint256 premium, address initiator, bytes calldata params)
+        external
+        returns (bool)
+    {
+        require(msg.sender == activeLender && initiator == address(this), "not lender");
+        _trade(abi.decode(params, (Trade)), premium);
+        IERC20(asset).approve(msg.sender, amount + premium);
+        return true;
+    }
+
+    /// @notice balancer flash loan callback, the loan and its fee are sent back to the vault
+    function receiveFlashLoan(
+        address[] memory tokens,
+        uint256[] memory amounts,
+        uint256[] memory feeAmounts,
+        bytes memory userData
+    ) external {
+        require(msg.sender == activeLender, "not lender");
+        _trade(abi.decode(userData, (Trade)), feeAmounts[0]);
+        _transfer(tokens[0], msg.sender, amounts[0] + feeAmounts[0]);
+    }
+
+    /// @notice uniswapV3 flash callback, the loan and its fee are sent back to the pool
+    function uniswapV3FlashCallback(uint256 fee0, uint256 fee1, bytes calldata data) external {
+        require(msg.sender == activeLender, "not lender");
+        Trade memory trade = abi.decode(data, (Trade));
+        uint256 fee = fee0 + fee1;
+        _trade(trade, fee);
+        _transfer(trade.assetLoan, msg.sender, trade.amountLoan + fee);
+    }
+
+    function uniswapV3SwapCallback(int256 amount0Delta, int256 amount1Delta, bytes calldata data) external {
+        _paySwap(amount0Delta, amount1Delta, data);
+    }
+
+    function algebraSwapCallback(int256 amount0Delta, int256 amount1Delta, bytes calldata data) external {
+        _paySwap(amount0Delta, amount1Delta, data);
+    }
+
+    /// @notice send `token` held by the contract to the owner
+    function withdraw(address token) external onlyOwner {
+        _transfer(token, owner, IERC20(token).balanceOf(address(this)));
+    }
+
+    /// swap the loan through both pools, the profit net of `fee` is kept to be returned by `arbitrage`
+    function _trade(Trade memory trade, uint256 fee) private {
+        uint256 tradeAmount = _swap(trade.buyPool, trade.assetLoan, trade.assetTrade, trade.amountLoan);
+        require(tradeAmount >= trade.buyAmountOutMinimum, "buy slippage");
+        uint256 amountOut = _swap(trade.sellPool, trade.assetTrade, trade.assetLoan, tradeAmount);
+        require(amountOut >= trade.sellAmountOutMinimum, "sell slippage");
+
+        require(amountOut >= trade.amountLoan + fee + trade.minProfit, "not profitable");
+        tradeProfit = amountOut - trade.amountLoan - fee;
+    }
+
+    /// swap `amountIn` of `tokenIn` for `tokenOut` in `pool`, returns the amount received
+    function _swap(address pool, address tokenIn, address tokenOut, uint256 amountIn) private returns (uint256) {
+        bool zeroForOne = tokenIn < tokenOut;
+        (bool isPair, bytes memory reserves) = pool.staticcall(abi.encodeCall(IUniswapV2Pair.getReserves, ()));
+        if (isPair && reserves.length >= 96) {
+            (uint256 reserve0, uint256 reserve1,) = abi.decode(reserves, (uint112, uint112, uint32));
+            (uint256 reserveIn, uint256 reserveOut) = zeroForOne ? (reserve0, reserve1) : (reserve1, reserve0);
+            // UniswapV2Library.getAmountOut
+            uint256 amountInWithFee = amountIn * 997;
+            uint256 amountOut = (amountInWithFee * reserveOut) / (reserveIn * 1000 + amountInWithFee);
+            _transfer(tokenIn, pool, amountIn);
+            IUniswapV2Pair(pool).swap(zeroForOne ? 0 : amountOut, zeroForOne ? amountOut : 0, address(this), "");
+            return amountOut;
+        }
+
+        activeSwapPool = pool;
+        (int256 amount0, int256 amount1) = IUniswapV3Pool(pool).swap(
+            address(this),
+            zeroForOne,
+            int256(amountIn),
+            zeroForOne ? MIN_SQRT_RATIO : MAX_SQRT_RATIO,
+            abi.encode(tokenIn)
+        );
+        activeSwapPool = address(0);
+        return uint256(-(zeroForOne ? amount1 : amount0));
+    }
+
+    /// pay the input of a swap to the pool swapping it
+    function _paySwap(int256 amount0Delta, int256 amount1Delta, bytes calldata data) private {
+        require(msg.sender == activeSwapPool, "not swap pool");
+        address tokenIn = abi.decode(data, (address));
+        _transfer(tokenIn, msg.sender, uint256(amount0Delta > 0 ? amount0Delta : amount1Delta));
+    }
+
+    function _transfer(address token, address to, uint256 amount) private {
+        (bool success, bytes memory output) = token.call(abi.encodeCall(IERC20.transfer, (to, amount)));
+        require(success && (output.length == 0 || abi.decode(output, (bool))), "transfer failed");
+    }
+}
@@ -6,6 +6,10 @@ const UNISWAPV2_FACTORY_ADDRESS: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5a
 const QUICKSWAPV3_FACTORY_ADDRESS: &str = "0x411b0fAcC3489691f28ad58c47006AF5E3Ab3A28";
 const QUICKSWAPV3_QUOTER_ADDRESS: &str = "0xa15F0D7377B2A0C0c10db057f641beD21028FC89";
 const QUICKSWAPV2_FACTORY_ADDRESS: &str = "0x5757371414417b8C6CAad45bAeF941aBc7d3Ab32";
+const WMATIC_ADDRESS: &str = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270";
+const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+const AAVEV3_POOL_ADDRESS: &str = "0x794a61358D6845594F94dc1DB02A252b5b4814aD";
+const BALANCER_VAULT_ADDRESS: &str = "0xBA12222222228d8Ba445958a75a0704d566BF2C8";
 
 pub fn get_contract_addresses() -> Option<(Address, Address, Address, Address, Address, Address)> {
     let Ok(uniswapv3_factory_address) = UNISWAPV3_FACTORY_ADDRESS.parse::<Address>() else {
@@ -36,3 +40,33 @@ pub fn get_contract_addresses() -> Option<(Address, Address, Address, Address, A
         quickswapv2_factory_address,
     ));
 }
+
+/// wrapped native asset of the chain, gas is priced on it
+pub fn get_native_asset_address() -> Option<Address> {
+    let Ok(wmatic_address) = WMATIC_ADDRESS.parse::<Address>() else {
+        return None
+    } ;
+
+    return Some(wmatic_address);
+}
+
+/// multicall3 is deployed at the same address on every chain
+pub fn get_multicall_address() -> Option<Address> {
+    let Ok(multicall_address) = MULTICALL3_ADDRESS.parse::<Address>() else {
+        return None
+    } ;
+
+    return Some(multicall_address);
+}
+
+/// flash loans lenders, aave V3 pool and balancer vault
+pub fn get_flash_loan_addresses() -> Option<(Address, Address)> {
+    let Ok(aavev3_pool_address) = AAVEV3_POOL_ADDRESS.parse::<Address>() else {
+        return None
+    } ;
+    let Ok(balancer_vault_address) = BALANCER_VAULT_ADDRESS.parse::<Address>() else {
+        return None
+    } ;
+
+    return Some((aavev3_pool_address, balancer_vault_address));
+}
@@ -0,0 +1,159 @@
+use crate::dexs::DexError;
+use ethers::{
+    types::{U256, U512},
+    utils::format_units,
+};
+use std::{cmp::Ordering, fmt};
+
+// 10^77 is the biggest power of ten that fits in 256 bits
+const MAX_DECIMALS: u32 = 77;
+const BPS: u32 = 10_000;
+
+/// amount of an asset in its smallest unit along with the asset decimals
+#[derive(Clone, Copy, Debug)]
+pub struct Amount {
+    pub raw: U256,
+    pub decimals: u32,
+}
+
+impl Amount {
+    pub fn new(raw: U256, decimals: u32) -> Amount {
+        return Amount { raw, decimals };
+    }
+
+    /// one unit of an asset with `decimals`
+    pub fn one(decimals: u32) -> Result<Amount, DexError> {
+        if decimals > MAX_DECIMALS {
+            return Err(DexError::DecimalsOverflow);
+        }
+        return Ok(Amount {
+            raw: U256::exp10(decimals as usize),
+            decimals,
+        });
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        return self.raw.is_zero();
+    }
+
+    /// raw amount with `decimals`(not less than the amount ones), 512 bits
+    /// fit any amount scaled to `MAX_DECIMALS`
+    #[inline]
+    fn scaled(&self, decimals: u32) -> U512 {
+        return U512::from(self.raw) * U512::exp10((decimals - self.decimals) as usize);
+    }
+
+    /// approximate value in units of the asset, only meant to display it
+    pub fn to_f64(&self) -> f64 {
+        let Ok(units) = format_units(self.raw, self.decimals) else {
+            return f64::NAN
+        };
+        return units.parse::<f64>().unwrap_or(f64::NAN);
+    }
+}
+
+impl Ord for Amount {
+    /// compare the values of amounts of assets with different decimals
+    fn cmp(&self, other: &Amount) -> Ordering {
+        let decimals = self.decimals.max(other.decimals);
+        return self.scaled(decimals).cmp(&other.scaled(decimals));
+    }
+}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Amount) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Amount) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Amount {}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Ok(units) = format_units(self.raw, self.decimals) else {
+            return write!(f, "{}e-{}", self.raw, self.decimals)
+        };
+        return write!(f, "{units}");
+    }
+}
+
+/// exact price of a base asset in a quote asset, the ratio of the `quote`
+/// amount paid for the `base` amount
+#[derive(Clone, Copy, Debug)]
+pub struct Price {
+    pub base: Amount,
+    pub quote: Amount,
+}
+
+impl Price {
+    pub fn new(base: Amount, quote: Amount) -> Price {
+        return Price { base, quote };
+    }
+
+    /// approximate price, only meant to display it
+    pub fn to_f64(&self) -> f64 {
+        return self.quote.to_f64() / self.base.to_f64();
+    }
+
+    /// relative difference to the `reference` price in basis points, rounded up,
+    /// `None` if the products overflow or a base or reference quote amount is zero
+    pub fn deviation_bps(&self, reference: &Price) -> Option<u32> {
+        if self.base.is_zero() || reference.base.is_zero() || reference.quote.is_zero() {
+            return None;
+        }
+        let decimals = self
+            .base
+            .decimals
+            .max(self.quote.decimals)
+            .max(reference.base.decimals)
+            .max(reference.quote.decimals);
+        let price = self.quote.scaled(decimals).checked_mul(reference.base.scaled(decimals))?;
+        let reference_price = reference.quote.scaled(decimals).checked_mul(self.base.scaled(decimals))?;
+        let difference = if price > reference_price {
+            price - reference_price
+        } else {
+            reference_price - price
+        };
+        let deviation = difference.checked_mul(U512::from(BPS))?;
+        let deviation = (deviation + reference_price - 1) / reference_price;
+        return Some(u32::try_from(deviation).unwrap_or(u32::MAX));
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Price) -> bool {
+        return self.partial_cmp(other) == Some(Ordering::Equal);
+    }
+}
+
+impl PartialOrd for Price {
+    /// compare quote0/base0 with quote1/base1 cross multiplying them, `None`
+    /// if the products overflow or a base amount is zero
+    fn partial_cmp(&self, other: &Price) -> Option<Ordering> {
+        if self.base.is_zero() || other.base.is_zero() {
+            return None;
+        }
+        let decimals = self
+            .base
+            .decimals
+            .max(self.quote.decimals)
+            .max(other.base.decimals)
+            .max(other.quote.decimals);
+        let lhs = self.quote.scaled(decimals).checked_mul(other.base.scaled(decimals))?;
+        let rhs = other.quote.scaled(decimals).checked_mul(self.base.scaled(decimals))?;
+        return Some(lhs.cmp(&rhs));
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.to_f64());
+    }
+}
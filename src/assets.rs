@@ -1,68 +1,92 @@
 use ethers::{
     contract::abigen,
-    providers::{Http, Middleware, Provider},
+    providers::{Http, Provider},
     types::Address,
 };
-use redis::{Commands, Connection};
-use std::sync::Arc;
+use crate::db::{AssetRecord, Store, StoreError};
+use crate::screening::{Screener, ScreeningError, TokenRisk};
+use futures::future::join_all;
+use std::{fmt, sync::Arc, time};
+
+// screening rounds of the assets that fail to be screened, and milliseconds
+// waited before retrying them
+const SCREENING_ATTEMPTS: u32 = 3;
+const SCREENING_RETRY_INTERVAL: u64 = 2_000;
 
 abigen!(
     Ierc20,"./data/abis/Ierc20.json" ;
 );
 
+/// error getting the data of an asset
+#[derive(Debug)]
+pub enum AssetError {
+    /// asset data could not be read from or written to the db
+    Db(StoreError),
+
+    /// symbol or decimals could not be read from the asset contract
+    Contract,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Db(err) => return write!(f, "db error: {err}"),
+            AssetError::Contract => return write!(f, "error reading asset contract"),
+        }
+    }
+}
+
+impl From<StoreError> for AssetError {
+    fn from(err: StoreError) -> AssetError {
+        return AssetError::Db(err);
+    }
+}
+
 #[derive(Clone)]
 pub struct Asset {
     pub contract: Ierc20<Provider<Http>>,
     pub address: Address,
     pub symbol: String,
     pub decimals: u32,
-}
-
-impl Asset {
-    pub async fn fromt_db_or_contract(
-        address: &Address,
-        provider: &Provider<Http>,
-        conn: &mut Connection,
-    ) -> Result<Asset, ()> {
-        if let Err(_) = conn.keys::<&[u8], String>(address.as_bytes()) {
-            let Ok(asset) = Self::from_contract(address, provider).await else {
-                return Err(())
-            };
 
-            conn.hset::<&[u8], &str, &str, bool>(address.as_bytes(), "symbol", &asset.symbol);
-            conn.hset::<&[u8], &str, &u32, bool>(address.as_bytes(), "decimals", &asset.decimals);
-            redis::cmd("SAVE").execute(conn);
+    /// risks found screening the asset, `None` if it was not screened
+    pub risks: Option<Vec<TokenRisk>>,
 
-            return Ok(asset);
-        }
+    /// error of the last screening of the asset if it failed, its risks are
+    /// unknown rather than found, it's screened again on the next run
+    pub screening_error: Option<ScreeningError>,
+}
 
-        let Ok(symbol) = conn.hget(address.as_bytes(),"symbol") else {
-            return Err(())
+impl Asset {
+    fn from_record(record: &AssetRecord, provider: &Provider<Http>) -> Asset {
+        return Asset {
+            contract: Ierc20::new(record.address, Arc::new(provider.clone())),
+            address: record.address,
+            symbol: record.symbol.clone(),
+            decimals: record.decimals,
+            risks: record.risks.clone(),
+            screening_error: None,
         };
+    }
 
-        let Ok(decimals) = conn.hget(address.as_bytes(),"decimals") else {
-            return Err(())
+    fn to_record(&self) -> AssetRecord {
+        return AssetRecord {
+            address: self.address,
+            symbol: self.symbol.clone(),
+            decimals: self.decimals,
+            risks: self.risks.clone(),
         };
-
-        let contract = Ierc20::new(address.clone(), Arc::new(provider.clone()));
-
-        return Ok(Asset {
-            symbol,
-            decimals,
-            contract,
-            address: *address,
-        });
     }
 
-    pub async fn from_contract(address: &Address, provider: &Provider<Http>) -> Result<Asset, ()> {
+    pub async fn from_contract(address: &Address, provider: &Provider<Http>) -> Result<Asset, AssetError> {
         let contract = Ierc20::new(*address, Arc::new(provider.clone()));
 
         let Ok(symbol ) = contract.symbol().call().await else {
-            return Err(())
+            return Err(AssetError::Contract)
         };
 
         let Ok(decimals) = contract.decimals().call().await else {
-            return Err(())
+            return Err(AssetError::Contract)
         };
 
         return Ok(Asset {
@@ -70,6 +94,8 @@ impl Asset {
             symbol,
             address: address.clone(),
             decimals: decimals as u32,
+            risks: None,
+            screening_error: None,
         });
     }
 
@@ -84,18 +110,131 @@ impl Asset {
     pub fn decimals(&self) -> u32 {
         return self.decimals.clone();
     }
+
+    /// whether the asset was screened and all its risks are `accepted_risks`
+    pub fn is_safe(&self, accepted_risks: &Vec<TokenRisk>) -> bool {
+        let Some(risks) = &self.risks else {
+            return false
+        };
+        return risks.iter().all(|risk| accepted_risks.contains(risk));
+    }
+}
+
+/// assets data cached in a store, assets not stored are read from their contracts
+pub struct AssetRepository {
+    store: Arc<dyn Store>,
+    provider: Provider<Http>,
 }
 
-pub async fn vec_from_addresses(
-    asset_addresses: Vec<Address>,
-    provider: &Provider<Http>,
-    conn: &mut Connection,
-) -> Vec<Asset> {
+impl AssetRepository {
+    pub fn new(store: Arc<dyn Store>, provider: Provider<Http>) -> AssetRepository {
+        AssetRepository { store, provider }
+    }
+
+    /// get the asset at `address` from the store or its contract
+    pub async fn get(&self, address: &Address) -> Result<Asset, AssetError> {
+        let Some(asset) = self.get_many(&vec![*address]).await.pop() else {
+            return Err(AssetError::Contract)
+        };
+        return asset;
+    }
+
+    /// get the assets at `addresses` in the same order, stored ones are read in a
+    /// single query and the rest from their contracts concurrently, then stored
+    /// in another one
+    pub async fn get_many(&self, addresses: &Vec<Address>) -> Vec<Result<Asset, AssetError>> {
+        // the contracts are the source of truth, a failing store is skipped
+        let records = match self.store.get_assets(addresses).await {
+            Ok(records) => records,
+            Err(err) => {
+                println!("error reading assets from db: {err}");
+                vec![None; addresses.len()]
+            }
+        };
+
+        let assets = join_all(addresses.iter().zip(records.iter()).map(|(address, record)| async move {
+            if let Some(record) = record {
+                return (Ok(Asset::from_record(record, &self.provider)), false);
+            }
+            return (Asset::from_contract(address, &self.provider).await, true);
+        }))
+        .await;
+
+        let fetched: Vec<AssetRecord> = assets
+            .iter()
+            .filter_map(|(asset, fetched)| match (asset, fetched) {
+                (Ok(asset), true) => Some(asset.to_record()),
+                _ => None,
+            })
+            .collect();
+        if !fetched.is_empty() {
+            if let Err(err) = self.store.put_assets(&fetched).await {
+                println!("error writing assets to db: {err}");
+            }
+        }
+
+        return assets.into_iter().map(|(asset, _)| asset).collect();
+    }
+
+    /// screen the `assets` not screened yet with `screener` and store their
+    /// risks, assets that fail to be screened are retried up to
+    /// `SCREENING_ATTEMPTS` times and keep their last screening error
+    pub async fn screen(&self, mut assets: Vec<Asset>, screener: &Screener) -> Vec<Asset> {
+        for attempt in 1..=SCREENING_ATTEMPTS {
+            if attempt > 1 {
+                if assets.iter().all(|asset| asset.risks.is_some()) {
+                    break;
+                }
+                tokio::time::sleep(time::Duration::from_millis(SCREENING_RETRY_INTERVAL)).await;
+            }
+            assets = self.screen_once(assets, screener).await;
+        }
+        return assets;
+    }
+
+    async fn screen_once(&self, assets: Vec<Asset>, screener: &Screener) -> Vec<Asset> {
+        let assets = join_all(assets.into_iter().map(|mut asset| async move {
+            if asset.risks.is_some() {
+                return (asset, false);
+            }
+            let risks = match screener.screen(&asset).await {
+                Ok(risks) => risks,
+                Err(err) => {
+                    println!("error screening asset {}: {err}", asset.symbol);
+                    asset.screening_error = Some(err);
+                    return (asset, false);
+                }
+            };
+            asset.risks = Some(risks);
+            asset.screening_error = None;
+            return (asset, true);
+        }))
+        .await;
+
+        let screened: Vec<AssetRecord> = assets
+            .iter()
+            .filter(|(_, screened)| *screened)
+            .map(|(asset, _)| asset.to_record())
+            .collect();
+        if !screened.is_empty() {
+            if let Err(err) = self.store.put_assets(&screened).await {
+                println!("error writing assets to db: {err}");
+            }
+        }
+
+        return assets.into_iter().map(|(asset, _)| asset).collect();
+    }
+}
+
+pub async fn vec_from_addresses(asset_addresses: Vec<Address>, repository: &AssetRepository) -> Vec<Asset> {
     let mut assets = Vec::<Asset>::with_capacity(asset_addresses.len());
-    for address in asset_addresses.iter() {
-        // NOTE: last changed line
-        let Ok(asset) = Asset::fromt_db_or_contract(address,&provider,conn).await else {
-            continue
+    for (address, asset) in asset_addresses.iter().zip(repository.get_many(&asset_addresses).await) {
+        let asset = match asset {
+            Ok(asset) => asset,
+            Err(err) => {
+                println!("error getting asset {:?}: {err}", address);
+                continue;
+            }
         };
         assets.push(asset);
     }
@@ -115,3 +254,75 @@ pub fn pairs_from_addresses(assets0: &Vec<Asset>, assets1: &Vec<Asset>) -> Vec<(
         .collect();
     return assets_pairs;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    // run with `cargo test -- --ignored`, a redis server is needed at REDIS_URL
+    // and the fallback test reads the erc20 at ASSET_ADDRESS on RPC_URL
+    fn env(key: &str) -> String {
+        return std::env::var(key).expect(&format!("{key} not set"));
+    }
+
+    async fn delete_asset(redis_url: &str, address: Address) {
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_tokio_connection().await.unwrap();
+        redis::cmd("DEL")
+            .arg(address.as_bytes())
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn get_stored_asset_from_redis() {
+        let redis_url = env("REDIS_URL");
+        let store = db::init(&redis_url).await.unwrap();
+        // nothing listens on the provider, the asset can only be read from the store
+        let provider = Provider::<Http>::try_from("http://localhost:1").unwrap();
+        let repository = AssetRepository::new(store.clone(), provider);
+
+        let record = AssetRecord {
+            address: Address::repeat_byte(0x7e),
+            symbol: String::from("TEST"),
+            decimals: 18,
+            risks: Some(vec![TokenRisk::Proxy]),
+        };
+        store.put_assets(&vec![record.clone()]).await.unwrap();
+
+        let asset = repository.get(&record.address).await;
+        delete_asset(&redis_url, record.address).await;
+        let asset = asset.unwrap();
+        assert_eq!(asset.address(), record.address);
+        assert_eq!(asset.symbol(), record.symbol);
+        assert_eq!(asset.decimals(), record.decimals);
+        assert_eq!(asset.risks, record.risks);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn get_missing_asset_from_contract_and_store_it() {
+        let redis_url = env("REDIS_URL");
+        let address: Address = env("ASSET_ADDRESS").parse().unwrap();
+        let store = db::init(&redis_url).await.unwrap();
+        let provider = Provider::<Http>::try_from(env("RPC_URL")).unwrap();
+        let repository = AssetRepository::new(store.clone(), provider.clone());
+        delete_asset(&redis_url, address).await;
+        assert!(store.get_assets(&vec![address]).await.unwrap()[0].is_none());
+
+        let asset = repository.get(&address).await.unwrap();
+        let contract_asset = Asset::from_contract(&address, &provider).await.unwrap();
+        assert_eq!(asset.symbol(), contract_asset.symbol());
+        assert_eq!(asset.decimals(), contract_asset.decimals());
+
+        let record = store.get_assets(&vec![address]).await.unwrap().pop().flatten();
+        delete_asset(&redis_url, address).await;
+        let record = record.unwrap();
+        assert_eq!(record.symbol, contract_asset.symbol());
+        assert_eq!(record.decimals, contract_asset.decimals());
+        assert_eq!(record.risks, None);
+    }
+}
@@ -1,37 +1,426 @@
-use anyhow::Result;
-use ethers;
-use reqwest::Url;
-use serde_json::Value;
+use crate::assets::Asset;
+use crate::contracts::Arbitrageur;
+use crate::flashloans::FlashLoan;
+use crate::optimizer::TradeSize;
+use crate::watchers::Route;
+use ethers::{
+    contract::ContractCall,
+    middleware::{NonceManagerMiddleware, SignerMiddleware},
+    providers::{Http, Middleware, Provider, ProviderError, RawCall},
+    signers::{LocalWallet, Signer},
+    types::{
+        spoof, transaction::eip2718::TypedTransaction, Address, BlockId, BlockNumber,
+        GethDebugTracingCallOptions, GethTrace,
+        TransactionReceipt, H256, I256, U256, U64,
+    },
+    utils::{format_units, keccak256},
+};
+use std::{fmt, sync::Arc};
 
-enum Status {
+const TRANSFER_EVENT_SIGNATURE: &str = "Transfer(address,address,uint256)";
+
+/// signer of the arbitrage transactions, nonces are assigned locally so watchers
+/// sending concurrently through the same caller don't reuse them
+pub type Client = NonceManagerMiddleware<SignerMiddleware<Provider<Http>, LocalWallet>>;
+
+/// error of the caller, reverted simulations are not errors but simulations
+/// with a revert reason
+#[derive(Debug)]
+pub enum CallerError {
+    /// the private key is not a valid secp256k1 key
+    InvalidKey,
+
+    /// rpc request failed
+    Rpc,
+
+    /// the arbitrage transaction could not be sent, with the error of the node
+    Send(String),
+
+    /// the receipt of the arbitrage transaction could not be read or it was dropped
+    Receipt,
+}
+
+impl fmt::Display for CallerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallerError::InvalidKey => return write!(f, "invalid private key"),
+            CallerError::Rpc => return write!(f, "rpc error"),
+            CallerError::Send(err) => return write!(f, "error sending transaction: {err}"),
+            CallerError::Receipt => return write!(f, "error getting transaction receipt"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Status {
     NotExecuted,
     Profit(f64),
     Loss(f64),
 }
 
-struct Caller {
-    signer: ethers.signer,
+/// result of simulating an arbitrage call on top of the pending block
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// profit returned by the arbitrageur contract in `asset_loan` units
+    pub profit: U256,
+
+    pub gas_used: U256,
+
+    /// decoded revert reason if the call reverted
+    pub revert_reason: Option<String>,
+
+    /// `debug_traceCall` trace of the call if tracing is enabled, reverted
+    /// calls are traced too
+    pub trace: Option<GethTrace>,
+}
+
+impl Simulation {
+    pub fn is_profitable(&self) -> bool {
+        return self.revert_reason.is_none() && !self.profit.is_zero();
+    }
+}
+
+/// minimum outputs of an arbitrage call, the arbitrageur contract
+/// reverts if any of them is not met
+#[derive(Clone, Debug)]
+pub struct SlippageGuard {
+    /// minimum amount of `asset_trade` received from the buy leg
+    pub buy_amount_out_minimum: U256,
+
+    /// minimum amount of `asset_loan` received from the sell leg
+    pub sell_amount_out_minimum: U256,
+
+    /// minimum profit of the trade in `asset_loan` units
+    pub min_profit: U256,
+}
+
+/// send arbitrage transactions to the arbitrageur contract signed by a local wallet
+pub struct Caller {
+    client: Arc<Client>,
+    arbitrageur: Arbitrageur<Client>,
+
+    /// state overrides applied to simulations and their traces
+    state_overrides: spoof::State,
+
+    /// trace simulations with `debug_traceCall`
+    trace_simulations: bool,
 }
 
 impl Caller {
+    pub async fn new(provider: Provider<Http>, private_key: &str, arbitrageur: Address) -> Result<Caller, CallerError> {
+        let Ok(wallet) = private_key.parse::<LocalWallet>() else {
+            return Err(CallerError::InvalidKey)
+        };
+        let Ok(chain_id) = provider.get_chainid().await else {
+            return Err(CallerError::Rpc)
+        };
+
+        let signer = SignerMiddleware::new(provider, wallet.with_chain_id(chain_id.as_u64()));
+        let address = signer.address();
+        let client = Arc::new(NonceManagerMiddleware::new(signer, address));
+        let arbitrageur = Arbitrageur::new(arbitrageur, client.clone());
+
+        return Ok(Caller {
+            client,
+            arbitrageur,
+            state_overrides: spoof::State::default(),
+            trace_simulations: false,
+        });
+    }
+
+    pub fn with_state_overrides(mut self, state_overrides: spoof::State) -> Caller {
+        self.state_overrides = state_overrides;
+        return self;
+    }
+
+    pub fn with_trace_simulations(mut self, trace_simulations: bool) -> Caller {
+        self.trace_simulations = trace_simulations;
+        return self;
+    }
+
+    /// simulate the arbitrage call with `eth_call` on top of the pending block,
+    /// reverts are returned as a simulation with its decoded reason
+    pub async fn simulate(
+        &self,
+        asset_loan: &Asset,
+        asset_trade: &Asset,
+        route: &Route,
+        trade_size: &TradeSize,
+        flash_loan: &FlashLoan,
+        guard: &SlippageGuard,
+    ) -> Result<Simulation, CallerError> {
+        let pending_block = BlockId::Number(BlockNumber::Pending);
+        let call = self
+            .arbitrage_call(asset_loan, asset_trade, route, trade_size, flash_loan, guard)
+            .block(pending_block);
+
+        let profit = match call.call_raw().block(pending_block).state(&self.state_overrides).await {
+            Ok(profit) => profit,
+            Err(err) => {
+                let revert_reason = err.decode_revert::<String>().unwrap_or(err.to_string());
+                return Ok(Simulation {
+                    profit: U256::zero(),
+                    gas_used: U256::zero(),
+                    revert_reason: Some(revert_reason),
+                    trace: self.trace(&call, pending_block).await,
+                });
+            }
+        };
 
-    fn new(url: Url) -> Self {
-        return Caller{signer : url}
+        let Ok(gas_used) = self.estimate_gas(&call.tx, pending_block).await else {
+            return Err(CallerError::Rpc)
+        };
+
+        let trace = self.trace(&call, pending_block).await;
+        return Ok(Simulation {
+            profit,
+            gas_used,
+            revert_reason: None,
+            trace,
+        });
     }
 
-    fn call(&self) -> Result<Value> {
-        todo!()
-        // search active oportunity
-        // use oportunity
-        // wait tx receipt
-        // self.client.call
+    /// estimate the gas of `tx` on top of `block` with the state overrides of the
+    /// simulations, calls only succeeding with them would fail the plain estimate
+    async fn estimate_gas(&self, tx: &TypedTransaction, block: BlockId) -> Result<U256, ProviderError> {
+        let params = estimate_gas_params(tx, block, &self.state_overrides)?;
+        return self.client.provider().request("eth_estimateGas", params).await;
     }
 
-    fn verify(&self) -> Option<Value> {
-        todo!()
-        // check if tx was completed
-        // get tx receipt
-        // determine tx result(profit or loss)
+    /// trace `call` with `debug_traceCall` on top of `block` with the state overrides
+    /// of the simulations, `None` if tracing is disabled or fails
+    async fn trace(&self, call: &ContractCall<Client, U256>, block: BlockId) -> Option<GethTrace> {
+        if !self.trace_simulations {
+            return None;
+        }
+
+        let options = GethDebugTracingCallOptions {
+            state_overrides: Some(self.state_overrides.clone()),
+            ..Default::default()
+        };
+        return match self.client.debug_trace_call(call.tx.clone(), Some(block), options).await {
+            Ok(trace) => Some(trace),
+            Err(err) => {
+                println!("error tracing arbitrage call: {err}");
+                None
+            }
+        };
     }
 
+    /// borrow `trade_size.amount_in` of `asset_loan` from the `flash_loan` lender and trade it
+    /// for `asset_trade` through `route` with the outputs bounded by `guard`, returns the
+    /// receipt once the transaction is mined
+    pub async fn call(
+        &self,
+        asset_loan: &Asset,
+        asset_trade: &Asset,
+        route: &Route,
+        trade_size: &TradeSize,
+        flash_loan: &FlashLoan,
+        guard: &SlippageGuard,
+    ) -> Result<TransactionReceipt, CallerError> {
+        let call = self.arbitrage_call(asset_loan, asset_trade, route, trade_size, flash_loan, guard);
+        let pending_tx = match call.send().await {
+            Ok(pending_tx) => pending_tx,
+            Err(err) => return Err(CallerError::Send(err.to_string())),
+        };
+
+        let Ok(Some(receipt)) = pending_tx.await else {
+            return Err(CallerError::Receipt)
+        };
+
+        return Ok(receipt);
+    }
+
+    fn arbitrage_call(
+        &self,
+        asset_loan: &Asset,
+        asset_trade: &Asset,
+        route: &Route,
+        trade_size: &TradeSize,
+        flash_loan: &FlashLoan,
+        guard: &SlippageGuard,
+    ) -> ContractCall<Client, U256> {
+        return self.arbitrageur.arbitrage(
+            asset_loan.address(),
+            asset_trade.address(),
+            trade_size.amount_in,
+            route.buy_quote.pool.address,
+            route.sell_quote.pool.address,
+            flash_loan.kind as u8,
+            flash_loan.lender,
+            guard.buy_amount_out_minimum,
+            guard.sell_amount_out_minimum,
+            guard.min_profit,
+        );
+    }
+
+    /// determine the result of an arbitrage transaction from the `asset_loan` transfers
+    /// of its receipt
+    pub fn verify(&self, receipt: &TransactionReceipt, asset_loan: &Asset) -> Status {
+        if receipt.status != Some(U64::one()) {
+            return Status::NotExecuted;
+        }
+
+        let balance_change = self.balance_change(receipt, asset_loan);
+        let Ok(amount) = format_units(balance_change.unsigned_abs(), asset_loan.decimals()) else {
+            return Status::NotExecuted
+        };
+        let amount: f64 = amount.parse().unwrap_or_default();
+        if balance_change.is_negative() {
+            return Status::Loss(amount);
+        }
+        return Status::Profit(amount);
+    }
+
+    /// net amount of `asset_loan` received by the wallet and the arbitrageur
+    /// contract in `receipt`
+    pub fn balance_change(&self, receipt: &TransactionReceipt, asset_loan: &Asset) -> I256 {
+        let owned = [self.client.inner().address(), self.arbitrageur.address()];
+        let transfer_topic = H256::from(keccak256(TRANSFER_EVENT_SIGNATURE));
+
+        let mut balance_change = I256::zero();
+        for log in receipt.logs.iter() {
+            if log.address != asset_loan.address() || log.topics.len() != 3 || log.topics[0] != transfer_topic {
+                continue;
+            }
+            let (from, to, value) = (
+                Address::from(log.topics[1]),
+                Address::from(log.topics[2]),
+                I256::from_raw(U256::from_big_endian(&log.data)),
+            );
+            match (owned.contains(&from), owned.contains(&to)) {
+                (false, true) => balance_change += value,
+                (true, false) => balance_change -= value,
+                _ => {}
+            }
+        }
+        return balance_change;
+    }
+}
+
+/// params of `eth_estimateGas` for `tx` on top of `block`, nodes take the state
+/// override set as third param as on `eth_call`, it's left out when empty
+fn estimate_gas_params(
+    tx: &TypedTransaction,
+    block: BlockId,
+    state_overrides: &spoof::State,
+) -> Result<Vec<serde_json::Value>, serde_json::Error> {
+    let mut params = vec![serde_json::to_value(tx)?, serde_json::to_value(block)?];
+    if *state_overrides != spoof::State::default() {
+        params.push(serde_json::to_value(state_overrides)?);
+    }
+    return Ok(params);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amounts::{Amount, Price};
+    use crate::assets::Ierc20;
+    use crate::dexs::{MockDex, Pool, Quote, Side};
+    use crate::flashloans::FlashLoanKind;
+
+    // run with `cargo test -- --ignored` against an anvil node at ANVIL_URL where
+    // contracts/Arbitrageur.sol is deployed at ARBITRAGEUR_ADDRESS by PRIVATE_KEY
+    fn env(key: &str) -> String {
+        return std::env::var(key).expect(&format!("{key} not set"));
+    }
+
+    fn asset(address: Address, provider: &Provider<Http>) -> Asset {
+        return Asset {
+            contract: Ierc20::new(address, Arc::new(provider.clone())),
+            address,
+            symbol: String::new(),
+            decimals: 18,
+            risks: None,
+            screening_error: None,
+        };
+    }
+
+    fn quote(side: Side, pool: Address) -> Quote {
+        let one = Amount::one(18).unwrap();
+        return Quote {
+            side,
+            in_amount: one,
+            out_amount: one,
+            price: Price::new(one, one),
+            pool: Pool {
+                address: pool,
+                fee_tier: None,
+            },
+            fee: 0,
+        };
+    }
+
+    #[test]
+    fn estimate_gas_with_state_overrides() {
+        let tx: TypedTransaction = ethers::types::TransactionRequest::new().to(Address::repeat_byte(0x01)).into();
+        let block = BlockId::Number(BlockNumber::Pending);
+
+        let params = estimate_gas_params(&tx, block, &spoof::State::default()).unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[1], serde_json::json!("pending"));
+
+        let holder = Address::repeat_byte(0x02);
+        let state_overrides = spoof::balance(holder, U256::exp10(18));
+        let params = estimate_gas_params(&tx, block, &state_overrides).unwrap();
+        assert_eq!(params.len(), 3);
+        assert_eq!(
+            params[2],
+            serde_json::json!({ format!("{:?}", holder): { "balance": "0xde0b6b3a7640000" } })
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn simulate_arbitrage_on_anvil() {
+        let provider = Provider::<Http>::try_from(env("ANVIL_URL")).unwrap();
+        let arbitrageur: Address = env("ARBITRAGEUR_ADDRESS").parse().unwrap();
+        let caller = Caller::new(provider.clone(), &env("PRIVATE_KEY"), arbitrageur).await.unwrap();
+        assert_eq!(caller.arbitrageur.owner().call().await.unwrap(), caller.client.inner().address());
+
+        let (asset_loan, asset_trade) = (asset(Address::repeat_byte(0x01), &provider), asset(Address::repeat_byte(0x02), &provider));
+        let (buy_pool, sell_pool) = (Address::repeat_byte(0x03), Address::repeat_byte(0x04));
+        let dex: Arc<dyn crate::dexs::Dex> = Arc::new(MockDex::new("mock", 0));
+        let route = Route {
+            buy_dex: dex.clone(),
+            buy_quote: quote(Side::Buy, buy_pool),
+            sell_dex: dex,
+            sell_quote: quote(Side::Sell, sell_pool),
+        };
+        let mut trade_size = TradeSize {
+            amount_in: U256::zero(),
+            trade_amount: U256::zero(),
+            amount_out: U256::zero(),
+            profit: I256::zero(),
+        };
+        let mut flash_loan = FlashLoan {
+            kind: FlashLoanKind::UniswapV3,
+            lender: Address::repeat_byte(0x05),
+            fee: 0,
+            liquidity: U256::MAX,
+        };
+        let guard = SlippageGuard {
+            buy_amount_out_minimum: U256::zero(),
+            sell_amount_out_minimum: U256::zero(),
+            min_profit: U256::zero(),
+        };
+
+        // the arguments are checked before borrowing
+        let simulation = caller
+            .simulate(&asset_loan, &asset_trade, &route, &trade_size, &flash_loan, &guard)
+            .await
+            .unwrap();
+        assert_eq!(simulation.revert_reason.as_deref(), Some("zero amount"));
+        assert!(!simulation.is_profitable());
+
+        trade_size.amount_in = U256::exp10(18);
+        flash_loan.lender = buy_pool;
+        let simulation = caller
+            .simulate(&asset_loan, &asset_trade, &route, &trade_size, &flash_loan, &guard)
+            .await
+            .unwrap();
+        assert_eq!(simulation.revert_reason.as_deref(), Some("lender traded"));
+    }
 }
@@ -1,8 +1,9 @@
-use config::{self, Config};
-use ethers::types::Address;
+use crate::screening::TokenRisk;
+use config::{self, Config, ConfigError};
+use ethers::types::{spoof, Address};
 use serde::Deserialize;
-use serde_json::{Map, Value};
-use std::{collections::HashMap, fs};
+use serde_json::Value;
+use std::{collections::HashMap, fmt, fs, io};
 
 // app
 const APP_NAME: &str = "arbitrageur";
@@ -18,19 +19,113 @@ const CONFIG_FILE_DEV: &str = "/home/mr-papi/SoftwareCode/Projects/arbitrageur-b
 const LOGFILE_KEY: &str = "logfile";
 const LOGFILE_DEFAULT_VALUE: &str =
     "/home/mr-papi/SoftwareCode/Projects/arbitrageur-bot/arbitrageur.log";
+const TRACE_SIMULATIONS_KEY: &str = "trace_simulations";
+const TRACE_SIMULATIONS_DEFAULT_VALUE: bool = false;
+const MIN_NET_PROFIT_KEY: &str = "min_net_profit";
+const MIN_NET_PROFIT_DEFAULT_VALUE: &str = "0";
+const CROSS_CHECK_QUOTES_KEY: &str = "cross_check_quotes";
+const CROSS_CHECK_QUOTES_DEFAULT_VALUE: bool = false;
+const MAX_CONCURRENT_WATCHERS_KEY: &str = "max_concurrent_watchers";
+const MAX_CONCURRENT_WATCHERS_DEFAULT_VALUE: u32 = 16;
+const MAX_POOL_SHARE_BPS_KEY: &str = "max_pool_share_bps";
+const MAX_POOL_SHARE_BPS_DEFAULT_VALUE: u32 = 1_000;
+const SLIPPAGE_TOLERANCE_BPS_KEY: &str = "slippage_tolerance_bps";
+const SLIPPAGE_TOLERANCE_BPS_DEFAULT_VALUE: u32 = 50;
+const MIN_POOL_LIQUIDITY_KEY: &str = "min_pool_liquidity";
+const MIN_POOL_LIQUIDITY_DEFAULT_VALUE: &str = "0";
+const ACCEPTED_TOKEN_RISKS_KEY: &str = "accepted_token_risks";
+const ACCEPTED_TOKEN_RISKS_DEFAULT_VALUE: [&str; 3] = ["proxy", "pausable", "blacklist"];
 const TRADE_ASSETS_KEY: &str = "trade";
 const LOAN_ASSETS_KEY: &str = "loan";
 
+/// error loading the configuration or the assets file
+#[derive(Debug)]
+pub enum ConfigurationError {
+    /// the user config directory could not be found
+    ConfigDir,
+
+    /// the configuration sources could not be read or deserialized
+    Config(ConfigError),
+
+    /// the assets file could not be read
+    AssetsFile(io::Error),
+
+    /// the assets file is not valid json
+    AssetsFormat(serde_json::Error),
+
+    /// the assets file has no assets map under the key
+    MissingAssets(&'static str),
+
+    /// the state overrides file could not be read
+    StateOverridesFile(io::Error),
+
+    /// the state overrides file is not a valid state override set
+    StateOverridesFormat(serde_json::Error),
+}
+
+impl fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigurationError::ConfigDir => return write!(f, "config directory not found"),
+            ConfigurationError::Config(err) => return write!(f, "{err}"),
+            ConfigurationError::AssetsFile(err) => return write!(f, "error reading assets file: {err}"),
+            ConfigurationError::AssetsFormat(err) => return write!(f, "error parsing assets file: {err}"),
+            ConfigurationError::MissingAssets(key) => return write!(f, "no {key} assets in assets file"),
+            ConfigurationError::StateOverridesFile(err) => return write!(f, "error reading state overrides file: {err}"),
+            ConfigurationError::StateOverridesFormat(err) => return write!(f, "error parsing state overrides file: {err}"),
+        }
+    }
+}
+
+impl From<ConfigError> for ConfigurationError {
+    fn from(err: ConfigError) -> ConfigurationError {
+        return ConfigurationError::Config(err);
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Configuration {
     pub rpc_url: String,
+    /// websocket rpc url to subscribe to pools events, poll if not set
+    pub ws_url: Option<String>,
     pub db_url: String,
     pub logfile: String,
     pub assets: String,
+    pub private_key: String,
+    pub arbitrageur: String,
+    pub trace_simulations: bool,
+    /// json file of the `eth_call` state override set applied to simulations, by
+    /// account address as in `{"0x..": {"balance": "0x..", "stateDiff": {"0x..": "0x.."}}}`
+    pub state_overrides: Option<String>,
+    /// minimum net profit of a trade in native asset units, as a decimal string
+    pub min_net_profit: String,
+    /// compare off-chain uniswapV3 quotes with the quoter ones
+    pub cross_check_quotes: bool,
+    /// maximum number of watchers and cycles evaluated at the same time
+    pub max_concurrent_watchers: usize,
+    /// maximum share of the balances of a pool a trade leg can swap, in basis points
+    pub max_pool_share_bps: u32,
+    /// tolerated decrease of the simulated trade outputs, in basis points
+    pub slippage_tolerance_bps: u32,
+    /// block the factories are scanned from to discover pools, pools are
+    /// looked up in the factories if not set
+    pub pools_start_block: Option<u64>,
+    /// minimum balance of the loan asset of a discovered pool, in loan asset
+    /// units, as a decimal string
+    pub min_pool_liquidity: String,
+    /// risks of the assets that do not prevent watching them, `proxy`, `pausable`
+    /// and `blacklist` by default as most stablecoins and bridged assets are
+    /// upgradeable proxies with pause and blacklist functions
+    pub accepted_token_risks: Vec<TokenRisk>,
+    /// rpc url of a local fork of the chain the assets are screened on, e.g. an
+    /// `anvil --fork-url <rpc_url>` node, they are screened on `rpc_url` if not set
+    pub screening_fork_url: Option<String>,
 }
 
-pub fn new() -> Result<Configuration, ()> {
-    let config_dir = dirs::config_dir().expect("failed to load XDG_CONFIG_DIR");
+pub fn new() -> Result<Configuration, ConfigurationError> {
+    let Some(config_dir) = dirs::config_dir() else {
+        return Err(ConfigurationError::ConfigDir)
+    };
 
     let app_config_file = {
         let mut app_config_path = config_dir.join(CONFIG_FILE_DIR);
@@ -40,40 +135,40 @@ pub fn new() -> Result<Configuration, ()> {
     };
 
     let conf_builder = Config::builder()
-        .set_default(LOGFILE_KEY, LOGFILE_DEFAULT_VALUE)
-        .expect("failod to set default logfile")
+        .set_default(LOGFILE_KEY, LOGFILE_DEFAULT_VALUE)?
+        .set_default(TRACE_SIMULATIONS_KEY, TRACE_SIMULATIONS_DEFAULT_VALUE)?
+        .set_default(MIN_NET_PROFIT_KEY, MIN_NET_PROFIT_DEFAULT_VALUE)?
+        .set_default(CROSS_CHECK_QUOTES_KEY, CROSS_CHECK_QUOTES_DEFAULT_VALUE)?
+        .set_default(MAX_CONCURRENT_WATCHERS_KEY, MAX_CONCURRENT_WATCHERS_DEFAULT_VALUE)?
+        .set_default(MAX_POOL_SHARE_BPS_KEY, MAX_POOL_SHARE_BPS_DEFAULT_VALUE)?
+        .set_default(SLIPPAGE_TOLERANCE_BPS_KEY, SLIPPAGE_TOLERANCE_BPS_DEFAULT_VALUE)?
+        .set_default(MIN_POOL_LIQUIDITY_KEY, MIN_POOL_LIQUIDITY_DEFAULT_VALUE)?
+        .set_default(ACCEPTED_TOKEN_RISKS_KEY, ACCEPTED_TOKEN_RISKS_DEFAULT_VALUE.to_vec())?
         .add_source(config::File::from(app_config_file).required(false))
         .add_source(config::File::with_name(CONFIG_FILE_DEV).required(false))
         .add_source(config::Environment::with_prefix(APP_PREFIX));
 
-    match conf_builder.build() {
-        Ok(cfg) => {
-            return Ok(cfg
-                .try_deserialize::<Configuration>()
-                .expect("error deserializing config"));
-        }
-        Err(e) => {
-            println!("configuration error: {}", e);
-            Err(())
-        }
-    }
+    let conf = conf_builder.build()?.try_deserialize::<Configuration>()?;
+    return Ok(conf);
 }
 
-pub fn get_assets(assets_path: &str) -> Result<(Vec<Address>, Vec<Address>), ()> {
-    let Ok(assets) = fs::read_to_string(assets_path) else {
-        return Err(())
+pub fn get_assets(assets_path: &str) -> Result<(Vec<Address>, Vec<Address>), ConfigurationError> {
+    let assets = match fs::read_to_string(assets_path) {
+        Ok(assets) => assets,
+        Err(err) => return Err(ConfigurationError::AssetsFile(err)),
     };
 
-    let Ok(assets_hashmap) = serde_json::from_str::<HashMap<String,Value>>(&assets) else {
-        return Err(())
+    let assets_hashmap = match serde_json::from_str::<HashMap<String, Value>>(&assets) {
+        Ok(assets_hashmap) => assets_hashmap,
+        Err(err) => return Err(ConfigurationError::AssetsFormat(err)),
     };
 
-    let Some(trade_assets_hashmap) = assets_hashmap[TRADE_ASSETS_KEY].as_object() else {
-        return Err(())
+    let Some(trade_assets_hashmap) = assets_hashmap.get(TRADE_ASSETS_KEY).and_then(Value::as_object) else {
+        return Err(ConfigurationError::MissingAssets(TRADE_ASSETS_KEY))
     };
 
-    let Some(loan_assets_hashmap) = assets_hashmap[LOAN_ASSETS_KEY].as_object() else {
-        return Err(())
+    let Some(loan_assets_hashmap) = assets_hashmap.get(LOAN_ASSETS_KEY).and_then(Value::as_object) else {
+        return Err(ConfigurationError::MissingAssets(LOAN_ASSETS_KEY))
     };
 
     let trade_assets: Vec<Address> = trade_assets_hashmap
@@ -89,6 +184,18 @@ pub fn get_assets(assets_path: &str) -> Result<(Vec<Address>, Vec<Address>), ()>
     return Ok((trade_assets, loan_assets));
 }
 
+pub fn get_state_overrides(state_overrides_path: &str) -> Result<spoof::State, ConfigurationError> {
+    let state_overrides = match fs::read_to_string(state_overrides_path) {
+        Ok(state_overrides) => state_overrides,
+        Err(err) => return Err(ConfigurationError::StateOverridesFile(err)),
+    };
+
+    return match serde_json::from_str::<spoof::State>(&state_overrides) {
+        Ok(state_overrides) => Ok(state_overrides),
+        Err(err) => Err(ConfigurationError::StateOverridesFormat(err)),
+    };
+}
+
 fn parse_address(address: &Value) -> Option<Address> {
     let Some(address_str) = address.as_str() else {
         return None
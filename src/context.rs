@@ -0,0 +1,53 @@
+use crate::multicall::Multicall;
+use crate::pools::PoolsState;
+use crate::registry::PoolRegistry;
+use ethers::providers::{Http, Provider};
+use std::sync::Arc;
+
+/// services shared by the dexs and flash loan providers, contracts are
+/// called directly through `provider` without the optional ones
+#[derive(Clone)]
+pub struct Context {
+    pub provider: Arc<Provider<Http>>,
+
+    /// batcher of the contracts calls
+    pub multicall: Option<Arc<Multicall>>,
+
+    /// local state of the pools to quote swaps off-chain
+    pub pools_state: Option<Arc<PoolsState>>,
+
+    /// registry of the pools discovered from the factories events, pools are
+    /// looked up in the factories without it
+    pub registry: Option<Arc<PoolRegistry>>,
+}
+
+impl Context {
+    pub fn new(provider: Arc<Provider<Http>>) -> Context {
+        Context {
+            provider,
+            multicall: None,
+            pools_state: None,
+            registry: None,
+        }
+    }
+
+    pub fn with_multicall(mut self, multicall: Arc<Multicall>) -> Context {
+        self.multicall = Some(multicall);
+        return self;
+    }
+
+    pub fn with_pools_state(mut self, pools_state: Arc<PoolsState>) -> Context {
+        self.pools_state = Some(pools_state);
+        return self;
+    }
+
+    pub fn with_registry(mut self, registry: Option<Arc<PoolRegistry>>) -> Context {
+        self.registry = registry;
+        return self;
+    }
+
+    #[inline]
+    pub fn multicall(&self) -> Option<&Multicall> {
+        return self.multicall.as_deref();
+    }
+}
@@ -1,15 +1,269 @@
-use redis::{Client, Connection};
+mod memory;
+mod redisdb;
+mod sqlite;
 
-const DB_ERR_MSG: &str = "failed to init db";
+use crate::screening::TokenRisk;
+use async_trait::async_trait;
+use ethers::types::{Address, H256, I256, U256};
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc};
 
-pub async fn init(db_url: &str) -> Result<Connection, &str> {
-    let Ok(client) = Client::open(db_url) else {
-        return Err(DB_ERR_MSG)
-    };
+pub use memory::MemoryStore;
+pub use redisdb::RedisStore;
+pub use sqlite::SqliteStore;
+
+/// error reading from or writing to a store
+#[derive(Debug)]
+pub enum StoreError {
+    /// the `db_url` scheme has no store
+    UnsupportedUrl(String),
+
+    Redis(redis::RedisError),
+
+    Sqlite(rusqlite::Error),
+
+    /// a stored record could not be encoded or decoded
+    Format(serde_json::Error),
+
+    /// the blocking task running a query did not complete
+    Task,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::UnsupportedUrl(db_url) => return write!(f, "unsupported db url: {db_url}"),
+            StoreError::Redis(err) => return write!(f, "redis error: {err}"),
+            StoreError::Sqlite(err) => return write!(f, "sqlite error: {err}"),
+            StoreError::Format(err) => return write!(f, "malformed record: {err}"),
+            StoreError::Task => return write!(f, "db task failed"),
+        }
+    }
+}
+
+impl From<redis::RedisError> for StoreError {
+    fn from(err: redis::RedisError) -> StoreError {
+        return StoreError::Redis(err);
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> StoreError {
+        return StoreError::Sqlite(err);
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> StoreError {
+        return StoreError::Format(err);
+    }
+}
+
+/// metadata of an asset
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AssetRecord {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u32,
+
+    /// risks found screening the asset, `None` if it was not screened
+    pub risks: Option<Vec<TokenRisk>>,
+}
+
+/// pool discovered in a dex
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolRecord {
+    pub address: Address,
+    pub dex: String,
+    pub token0: Address,
+    pub token1: Address,
+
+    /// fee tier of the pool on dexs with a pool per fee tier(uniswapV3)
+    pub fee_tier: Option<u32>,
+
+    /// block the pool was created at
+    pub block: u64,
+}
+
+/// what a watcher did with an opportunity
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Traded,
+    Skipped,
+}
+
+/// trade opportunity evaluated by a watcher
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpportunityRecord {
+    /// unix time of the evaluation in seconds
+    pub timestamp: u64,
+    pub block: u64,
+    pub asset_trade: Address,
+    pub asset_loan: Address,
+    pub buy_dex: String,
+    pub buy_pool: Address,
+    pub sell_dex: String,
+    pub sell_pool: Address,
+
+    /// `asset_trade` received buying with 1 unit of `asset_loan`
+    pub buy_quote: U256,
+
+    /// `asset_loan` received selling 1 unit of `asset_trade`
+    pub sell_quote: U256,
+
+    /// fees of the pools and the flash loan, in hundredths of a bip
+    pub fees: u32,
+
+    /// amount of `asset_loan` borrowed, `None` if the trade was not sized
+    pub amount_in: Option<U256>,
+
+    /// expected profit of `amount_in` in `asset_loan` units
+    pub profit: Option<I256>,
+
+    /// expected profit over `amount_in`, in basis points
+    pub roi_bps: Option<i64>,
 
-    let Ok(conn) = client.get_connection() else {
-        return Err(DB_ERR_MSG)
+    /// profit returned by the simulated arbitrage call in `asset_loan` units,
+    /// `None` if the trade was not simulated
+    pub simulated_profit: Option<U256>,
+
+    /// gas used by the simulated arbitrage call
+    pub gas_used: Option<U256>,
+
+    /// reason the simulated arbitrage call reverted
+    pub revert_reason: Option<String>,
+
+    pub decision: Decision,
+
+    /// reason the opportunity was skipped
+    pub reason: Option<String>,
+}
+
+/// arbitrage transaction sent by a watcher
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeRecord {
+    /// unix time the transaction receipt was received in seconds
+    pub timestamp: u64,
+    pub block: u64,
+    pub tx_hash: H256,
+    pub asset_trade: Address,
+    pub asset_loan: Address,
+    pub buy_dex: String,
+    pub sell_dex: String,
+    pub amount_in: U256,
+    pub gas_used: U256,
+    pub gas_price: U256,
+
+    /// change of the `asset_loan` balance of the wallet and the arbitrageur
+    pub profit: I256,
+}
+
+/// filter of the opportunities and trades history, unset fields match any record
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    /// assets of the traded pair, in any order
+    pub pair: Option<(Address, Address)>,
+
+    /// dex of either leg of the trade
+    pub dex: Option<String>,
+
+    /// unix time range in seconds, both ends included
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, timestamp: u64, asset_trade: Address, asset_loan: Address, buy_dex: &str, sell_dex: &str) -> bool {
+        if self.from.map_or(false, |from| timestamp < from) || self.to.map_or(false, |to| timestamp > to) {
+            return false;
+        }
+        if let Some((asset0, asset1)) = self.pair {
+            if (asset_trade, asset_loan) != (asset0, asset1) && (asset_trade, asset_loan) != (asset1, asset0) {
+                return false;
+            }
+        }
+        if let Some(dex) = &self.dex {
+            return buy_dex == dex || sell_dex == dex;
+        }
+        return true;
+    }
+
+    pub fn matches_opportunity(&self, opportunity: &OpportunityRecord) -> bool {
+        return self.matches(
+            opportunity.timestamp,
+            opportunity.asset_trade,
+            opportunity.asset_loan,
+            &opportunity.buy_dex,
+            &opportunity.sell_dex,
+        );
+    }
+
+    pub fn matches_trade(&self, trade: &TradeRecord) -> bool {
+        return self.matches(trade.timestamp, trade.asset_trade, trade.asset_loan, &trade.buy_dex, &trade.sell_dex);
+    }
+}
+
+/// storage of the bot data, implement it to make a backend available
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// get the assets at `addresses` in the same order, `None` for the ones not stored
+    async fn get_assets(&self, addresses: &Vec<Address>) -> Result<Vec<Option<AssetRecord>>, StoreError>;
+
+    /// store `assets`, replacing the ones stored at the same addresses
+    async fn put_assets(&self, assets: &Vec<AssetRecord>) -> Result<(), StoreError>;
+
+    async fn get_pools(&self) -> Result<Vec<PoolRecord>, StoreError>;
+
+    /// store `pools`, replacing the ones stored at the same addresses
+    async fn put_pools(&self, pools: &Vec<PoolRecord>) -> Result<(), StoreError>;
+
+    async fn put_opportunity(&self, opportunity: &OpportunityRecord) -> Result<(), StoreError>;
+
+    /// get the opportunities matching `filter` ordered by time
+    async fn get_opportunities(&self, filter: &HistoryFilter) -> Result<Vec<OpportunityRecord>, StoreError>;
+
+    async fn put_trade(&self, trade: &TradeRecord) -> Result<(), StoreError>;
+
+    /// get the trades matching `filter` ordered by time
+    async fn get_trades(&self, filter: &HistoryFilter) -> Result<Vec<TradeRecord>, StoreError>;
+}
+
+/// open the store of `db_url`, its scheme selects the backend:
+/// `redis://`(or `rediss://`, `redis+unix://`), `sqlite://<path>` and `memory://`
+pub async fn init(db_url: &str) -> Result<Arc<dyn Store>, StoreError> {
+    let Some((scheme, path)) = db_url.split_once("://") else {
+        return Err(StoreError::UnsupportedUrl(db_url.to_string()))
     };
 
-    return Ok(conn);
+    match scheme {
+        "redis" | "rediss" | "redis+unix" => return Ok(Arc::new(RedisStore::new(db_url).await?)),
+        "sqlite" => return Ok(Arc::new(SqliteStore::new(path).await?)),
+        "memory" => return Ok(Arc::new(MemoryStore::new())),
+        _ => return Err(StoreError::UnsupportedUrl(db_url.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_filter_matches() {
+        let (weth, usdc, dai) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        assert!(HistoryFilter::default().matches(10, weth, usdc, "uniswap", "quickswap"));
+
+        let filter = HistoryFilter {
+            pair: Some((usdc, weth)),
+            dex: Some(String::from("quickswap")),
+            from: Some(10),
+            to: Some(20),
+        };
+        assert!(filter.matches(10, weth, usdc, "uniswap", "quickswap"));
+        assert!(filter.matches(20, usdc, weth, "quickswap", "uniswap"));
+        assert!(!filter.matches(9, weth, usdc, "uniswap", "quickswap"));
+        assert!(!filter.matches(21, weth, usdc, "uniswap", "quickswap"));
+        assert!(!filter.matches(15, weth, dai, "uniswap", "quickswap"));
+        assert!(!filter.matches(15, weth, usdc, "uniswap", "uniswapv2"));
+    }
 }
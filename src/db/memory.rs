@@ -0,0 +1,162 @@
+use super::{AssetRecord, HistoryFilter, OpportunityRecord, PoolRecord, Store, StoreError, TradeRecord};
+use async_trait::async_trait;
+use ethers::types::Address;
+use std::{collections::HashMap, sync::RwLock};
+
+/// store kept in memory, its data is lost on exit
+pub struct MemoryStore {
+    assets: RwLock<HashMap<Address, AssetRecord>>,
+    pools: RwLock<HashMap<Address, PoolRecord>>,
+    opportunities: RwLock<Vec<OpportunityRecord>>,
+    trades: RwLock<Vec<TradeRecord>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            assets: RwLock::new(HashMap::new()),
+            pools: RwLock::new(HashMap::new()),
+            opportunities: RwLock::new(Vec::new()),
+            trades: RwLock::new(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get_assets(&self, addresses: &Vec<Address>) -> Result<Vec<Option<AssetRecord>>, StoreError> {
+        let assets = self.assets.read().unwrap();
+        return Ok(addresses.iter().map(|address| assets.get(address).cloned()).collect());
+    }
+
+    async fn put_assets(&self, assets: &Vec<AssetRecord>) -> Result<(), StoreError> {
+        let mut stored_assets = self.assets.write().unwrap();
+        for asset in assets.iter() {
+            stored_assets.insert(asset.address, asset.clone());
+        }
+        return Ok(());
+    }
+
+    async fn get_pools(&self) -> Result<Vec<PoolRecord>, StoreError> {
+        return Ok(self.pools.read().unwrap().values().cloned().collect());
+    }
+
+    async fn put_pools(&self, pools: &Vec<PoolRecord>) -> Result<(), StoreError> {
+        let mut stored_pools = self.pools.write().unwrap();
+        for pool in pools.iter() {
+            stored_pools.insert(pool.address, pool.clone());
+        }
+        return Ok(());
+    }
+
+    async fn put_opportunity(&self, opportunity: &OpportunityRecord) -> Result<(), StoreError> {
+        self.opportunities.write().unwrap().push(opportunity.clone());
+        return Ok(());
+    }
+
+    /// records are pushed as they are evaluated so they are already ordered by time
+    async fn get_opportunities(&self, filter: &HistoryFilter) -> Result<Vec<OpportunityRecord>, StoreError> {
+        let opportunities = self.opportunities.read().unwrap();
+        return Ok(opportunities
+            .iter()
+            .filter(|opportunity| filter.matches_opportunity(opportunity))
+            .cloned()
+            .collect());
+    }
+
+    async fn put_trade(&self, trade: &TradeRecord) -> Result<(), StoreError> {
+        self.trades.write().unwrap().push(trade.clone());
+        return Ok(());
+    }
+
+    async fn get_trades(&self, filter: &HistoryFilter) -> Result<Vec<TradeRecord>, StoreError> {
+        let trades = self.trades.read().unwrap();
+        return Ok(trades.iter().filter(|trade| filter.matches_trade(trade)).cloned().collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Decision;
+    use ethers::types::{H256, I256, U256};
+
+    fn opportunity(timestamp: u64, asset_trade: Address, asset_loan: Address, buy_dex: &str) -> OpportunityRecord {
+        return OpportunityRecord {
+            timestamp,
+            block: timestamp,
+            asset_trade,
+            asset_loan,
+            buy_dex: String::from(buy_dex),
+            buy_pool: Address::zero(),
+            sell_dex: String::from("quickswap"),
+            sell_pool: Address::zero(),
+            buy_quote: U256::zero(),
+            sell_quote: U256::zero(),
+            fees: 0,
+            amount_in: None,
+            profit: None,
+            roi_bps: None,
+            simulated_profit: None,
+            gas_used: None,
+            revert_reason: None,
+            decision: Decision::Skipped,
+            reason: Some(String::from("sell price not above buy price")),
+        };
+    }
+
+    fn trade(timestamp: u64, asset_trade: Address, asset_loan: Address) -> TradeRecord {
+        return TradeRecord {
+            timestamp,
+            block: timestamp,
+            tx_hash: H256::zero(),
+            asset_trade,
+            asset_loan,
+            buy_dex: String::from("uniswap"),
+            sell_dex: String::from("quickswap"),
+            amount_in: U256::zero(),
+            gas_used: U256::zero(),
+            gas_price: U256::zero(),
+            profit: I256::zero(),
+        };
+    }
+
+    #[tokio::test]
+    async fn query_history() {
+        let (weth, usdc, dai) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let store = MemoryStore::new();
+        for opportunity in [
+            opportunity(10, weth, usdc, "uniswap"),
+            opportunity(20, weth, dai, "uniswap"),
+            opportunity(30, weth, usdc, "uniswapv2"),
+        ] {
+            store.put_opportunity(&opportunity).await.unwrap();
+        }
+        for trade in [trade(10, weth, usdc), trade(20, weth, dai)] {
+            store.put_trade(&trade).await.unwrap();
+        }
+
+        let timestamps = |records: Vec<OpportunityRecord>| -> Vec<u64> {
+            return records.iter().map(|record| record.timestamp).collect();
+        };
+        let all = store.get_opportunities(&HistoryFilter::default()).await.unwrap();
+        assert_eq!(timestamps(all), vec![10, 20, 30]);
+
+        let pair = HistoryFilter {
+            pair: Some((usdc, weth)),
+            ..Default::default()
+        };
+        assert_eq!(timestamps(store.get_opportunities(&pair).await.unwrap()), vec![10, 30]);
+
+        let dex = HistoryFilter {
+            dex: Some(String::from("uniswap")),
+            from: Some(15),
+            ..Default::default()
+        };
+        assert_eq!(timestamps(store.get_opportunities(&dex).await.unwrap()), vec![20]);
+
+        let trades = store.get_trades(&pair).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].asset_loan, usdc);
+    }
+}
@@ -0,0 +1,168 @@
+use super::{AssetRecord, HistoryFilter, OpportunityRecord, PoolRecord, Store, StoreError, TradeRecord};
+use async_trait::async_trait;
+use ethers::types::Address;
+use redis::{aio::MultiplexedConnection, Client};
+use std::collections::HashMap;
+
+// assets are hashes keyed by their address with a field per asset field
+const SYMBOL_FIELD: &str = "symbol";
+const DECIMALS_FIELD: &str = "decimals";
+const RISKS_FIELD: &str = "risks";
+
+// pools are json encoded in a hash keyed by their address, opportunities
+// and trades in sorted sets scored by their timestamp
+const POOLS_KEY: &str = "pools";
+const OPPORTUNITIES_KEY: &str = "opportunities";
+const TRADES_KEY: &str = "trades";
+
+/// store on a redis server, writes are not persisted with `SAVE`,
+/// persistence is left to the server configuration
+pub struct RedisStore {
+    conn: MultiplexedConnection,
+}
+
+impl RedisStore {
+    pub async fn new(db_url: &str) -> Result<RedisStore, StoreError> {
+        let client = Client::open(db_url)?;
+        let conn = client.get_multiplexed_tokio_connection().await?;
+        return Ok(RedisStore { conn });
+    }
+
+    /// json records of the sorted set at `key` in the time range of `filter`
+    async fn get_range(&self, key: &str, filter: &HistoryFilter) -> Result<Vec<String>, StoreError> {
+        let records = redis::cmd("ZRANGEBYSCORE")
+            .arg(key)
+            .arg(filter.from.unwrap_or(0))
+            .arg(filter.to.map_or(String::from("+inf"), |to| to.to_string()))
+            .query_async::<_, Vec<String>>(&mut self.conn.clone())
+            .await?;
+        return Ok(records);
+    }
+}
+
+#[async_trait]
+impl Store for RedisStore {
+    /// assets are read in a single round trip, hashes with missing or
+    /// malformed fields are not stored assets
+    async fn get_assets(&self, addresses: &Vec<Address>) -> Result<Vec<Option<AssetRecord>>, StoreError> {
+        let mut pipe = redis::pipe();
+        addresses.iter().for_each(|address| {
+            pipe.hgetall(address.as_bytes());
+        });
+        let hashes = pipe
+            .query_async::<_, Vec<HashMap<String, String>>>(&mut self.conn.clone())
+            .await?;
+
+        let assets = addresses
+            .iter()
+            .zip(hashes)
+            .map(|(address, fields)| {
+                let symbol = fields.get(SYMBOL_FIELD)?;
+                let Ok(decimals) = fields.get(DECIMALS_FIELD)?.parse::<u32>() else {
+                    return None
+                };
+                // risks are json encoded, assets stored before being screened have none
+                let risks = fields.get(RISKS_FIELD).and_then(|risks| serde_json::from_str(risks).ok()).flatten();
+                return Some(AssetRecord {
+                    address: *address,
+                    symbol: symbol.clone(),
+                    decimals,
+                    risks,
+                });
+            })
+            .collect();
+        return Ok(assets);
+    }
+
+    async fn put_assets(&self, assets: &Vec<AssetRecord>) -> Result<(), StoreError> {
+        if assets.is_empty() {
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        for asset in assets.iter() {
+            pipe.hset_multiple(
+                asset.address.as_bytes(),
+                &[
+                    (SYMBOL_FIELD, asset.symbol.clone()),
+                    (DECIMALS_FIELD, asset.decimals.to_string()),
+                    (RISKS_FIELD, serde_json::to_string(&asset.risks)?),
+                ],
+            )
+            .ignore();
+        }
+        pipe.query_async::<_, ()>(&mut self.conn.clone()).await?;
+        return Ok(());
+    }
+
+    async fn get_pools(&self) -> Result<Vec<PoolRecord>, StoreError> {
+        let pools = redis::cmd("HVALS")
+            .arg(POOLS_KEY)
+            .query_async::<_, Vec<String>>(&mut self.conn.clone())
+            .await?;
+        let mut pools_records = Vec::<PoolRecord>::with_capacity(pools.len());
+        for pool in pools.iter() {
+            pools_records.push(serde_json::from_str(pool)?);
+        }
+        return Ok(pools_records);
+    }
+
+    async fn put_pools(&self, pools: &Vec<PoolRecord>) -> Result<(), StoreError> {
+        if pools.is_empty() {
+            return Ok(());
+        }
+        let mut items = Vec::<(String, String)>::with_capacity(pools.len());
+        for pool in pools.iter() {
+            items.push((format!("{:?}", pool.address), serde_json::to_string(pool)?));
+        }
+        redis::cmd("HSET")
+            .arg(POOLS_KEY)
+            .arg(items)
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await?;
+        return Ok(());
+    }
+
+    async fn put_opportunity(&self, opportunity: &OpportunityRecord) -> Result<(), StoreError> {
+        redis::cmd("ZADD")
+            .arg(OPPORTUNITIES_KEY)
+            .arg(opportunity.timestamp)
+            .arg(serde_json::to_string(opportunity)?)
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await?;
+        return Ok(());
+    }
+
+    /// opportunities are read by time range, the pair and dex are filtered on them
+    async fn get_opportunities(&self, filter: &HistoryFilter) -> Result<Vec<OpportunityRecord>, StoreError> {
+        let mut opportunities = Vec::<OpportunityRecord>::new();
+        for record in self.get_range(OPPORTUNITIES_KEY, filter).await?.iter() {
+            let opportunity: OpportunityRecord = serde_json::from_str(record)?;
+            if filter.matches_opportunity(&opportunity) {
+                opportunities.push(opportunity);
+            }
+        }
+        return Ok(opportunities);
+    }
+
+    async fn put_trade(&self, trade: &TradeRecord) -> Result<(), StoreError> {
+        redis::cmd("ZADD")
+            .arg(TRADES_KEY)
+            .arg(trade.timestamp)
+            .arg(serde_json::to_string(trade)?)
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await?;
+        return Ok(());
+    }
+
+    /// trades are read by time range, the pair and dex are filtered on them
+    async fn get_trades(&self, filter: &HistoryFilter) -> Result<Vec<TradeRecord>, StoreError> {
+        let mut trades = Vec::<TradeRecord>::new();
+        for record in self.get_range(TRADES_KEY, filter).await?.iter() {
+            let trade: TradeRecord = serde_json::from_str(record)?;
+            if filter.matches_trade(&trade) {
+                trades.push(trade);
+            }
+        }
+        return Ok(trades);
+    }
+}
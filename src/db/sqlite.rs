@@ -0,0 +1,323 @@
+use super::{AssetRecord, HistoryFilter, OpportunityRecord, PoolRecord, Store, StoreError, TradeRecord};
+use async_trait::async_trait;
+use ethers::types::Address;
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, Mutex};
+
+// records are stored json encoded along with the columns they are looked up by
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS assets (
+        address TEXT PRIMARY KEY,
+        symbol TEXT NOT NULL,
+        decimals INTEGER NOT NULL,
+        risks TEXT
+    );
+    CREATE TABLE IF NOT EXISTS pools (
+        address TEXT PRIMARY KEY,
+        record TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS opportunities (
+        timestamp INTEGER NOT NULL,
+        asset_trade TEXT NOT NULL,
+        asset_loan TEXT NOT NULL,
+        buy_dex TEXT NOT NULL,
+        sell_dex TEXT NOT NULL,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS opportunities_timestamp ON opportunities (timestamp);
+    CREATE TABLE IF NOT EXISTS trades (
+        timestamp INTEGER NOT NULL,
+        asset_trade TEXT NOT NULL,
+        asset_loan TEXT NOT NULL,
+        buy_dex TEXT NOT NULL,
+        sell_dex TEXT NOT NULL,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS trades_timestamp ON trades (timestamp);
+";
+
+// columns added to the tables after they were first created, (table,column,type),
+// tables created by previous versions are altered to add them
+const MIGRATIONS: [(&str, &str, &str); 1] = [("assets", "risks", "TEXT")];
+
+// history tables are looked up with the same filter, unset parameters match any row
+const HISTORY_WHERE: &str = "
+    WHERE timestamp >= ?1 AND timestamp <= ?2
+    AND (?3 IS NULL OR (asset_trade = ?3 AND asset_loan = ?4) OR (asset_trade = ?4 AND asset_loan = ?3))
+    AND (?5 IS NULL OR buy_dex = ?5 OR sell_dex = ?5)
+    ORDER BY timestamp
+";
+
+/// store on an embedded sqlite database file, `:memory:` keeps it in memory
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub async fn new(path: &str) -> Result<SqliteStore, StoreError> {
+        let conn = if path == ":memory:" {
+            Connection::open_in_memory()?
+        } else {
+            Connection::open(path)?
+        };
+        let store = SqliteStore {
+            conn: Arc::new(Mutex::new(conn)),
+        };
+        store
+            .run(|conn| {
+                conn.execute_batch(SCHEMA)?;
+                return Self::migrate(conn);
+            })
+            .await?;
+        return Ok(store);
+    }
+
+    /// add the `MIGRATIONS` columns missing in the tables, listed by `PRAGMA table_info`
+    fn migrate(conn: &mut Connection) -> Result<(), StoreError> {
+        for (table, column, column_type) in MIGRATIONS {
+            let mut columns = Vec::<String>::new();
+            {
+                let mut statement = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+                for name in statement.query_map(params![], |row| row.get::<usize, String>(1))? {
+                    columns.push(name?);
+                }
+            }
+            if !columns.iter().any(|name| name == column) {
+                conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {column_type}"), params![])?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// run `query` on the connection in a blocking task of the runtime
+    async fn run<T, F>(&self, query: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, StoreError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let Ok(result) = tokio::task::spawn_blocking(move || query(&mut conn.lock().unwrap())).await else {
+            return Err(StoreError::Task)
+        };
+        return result;
+    }
+
+    /// records of the history `table` matching `filter`
+    async fn get_history<T>(&self, table: &'static str, filter: &HistoryFilter) -> Result<Vec<T>, StoreError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let (from, to) = (
+            filter.from.map_or(i64::MIN, |from| from as i64),
+            filter.to.map_or(i64::MAX, |to| to as i64),
+        );
+        let (asset0, asset1) = match filter.pair {
+            Some((asset0, asset1)) => (Some(format!("{:?}", asset0)), Some(format!("{:?}", asset1))),
+            None => (None, None),
+        };
+        let dex = filter.dex.clone();
+        return self
+            .run(move |conn| {
+                let mut statement = conn.prepare(&format!("SELECT record FROM {table} {HISTORY_WHERE}"))?;
+                let records = statement.query_map(params![from, to, asset0, asset1, dex], |row| {
+                    row.get::<usize, String>(0)
+                })?;
+                let mut history = Vec::<T>::new();
+                for record in records {
+                    history.push(serde_json::from_str(&record?)?);
+                }
+                return Ok(history);
+            })
+            .await;
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn get_assets(&self, addresses: &Vec<Address>) -> Result<Vec<Option<AssetRecord>>, StoreError> {
+        let addresses = addresses.clone();
+        return self
+            .run(move |conn| {
+                let mut statement = conn.prepare("SELECT symbol, decimals, risks FROM assets WHERE address = ?1")?;
+                let mut assets = Vec::<Option<AssetRecord>>::with_capacity(addresses.len());
+                for address in addresses.into_iter() {
+                    let row = statement
+                        .query_map(params![format!("{:?}", address)], |row| {
+                            return Ok((
+                                row.get::<usize, String>(0)?,
+                                row.get::<usize, u32>(1)?,
+                                row.get::<usize, Option<String>>(2)?,
+                            ));
+                        })?
+                        .next()
+                        .transpose()?;
+                    let Some((symbol, decimals, risks)) = row else {
+                        assets.push(None);
+                        continue
+                    };
+                    let risks = match risks {
+                        Some(risks) => serde_json::from_str(&risks)?,
+                        None => None,
+                    };
+                    assets.push(Some(AssetRecord {
+                        address,
+                        symbol,
+                        decimals,
+                        risks,
+                    }));
+                }
+                return Ok(assets);
+            })
+            .await;
+    }
+
+    async fn put_assets(&self, assets: &Vec<AssetRecord>) -> Result<(), StoreError> {
+        let mut records = Vec::<(AssetRecord, String)>::with_capacity(assets.len());
+        for asset in assets.iter() {
+            records.push((asset.clone(), serde_json::to_string(&asset.risks)?));
+        }
+        return self
+            .run(move |conn| {
+                let transaction = conn.transaction()?;
+                for (asset, risks) in records.iter() {
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO assets (address, symbol, decimals, risks) VALUES (?1, ?2, ?3, ?4)",
+                        params![format!("{:?}", asset.address), asset.symbol, asset.decimals, risks],
+                    )?;
+                }
+                transaction.commit()?;
+                return Ok(());
+            })
+            .await;
+    }
+
+    async fn get_pools(&self) -> Result<Vec<PoolRecord>, StoreError> {
+        return self
+            .run(|conn| {
+                let mut statement = conn.prepare("SELECT record FROM pools")?;
+                let records = statement.query_map(params![], |row| row.get::<usize, String>(0))?;
+                let mut pools = Vec::<PoolRecord>::new();
+                for record in records {
+                    pools.push(serde_json::from_str(&record?)?);
+                }
+                return Ok(pools);
+            })
+            .await;
+    }
+
+    async fn put_pools(&self, pools: &Vec<PoolRecord>) -> Result<(), StoreError> {
+        let mut records = Vec::<(String, String)>::with_capacity(pools.len());
+        for pool in pools.iter() {
+            records.push((format!("{:?}", pool.address), serde_json::to_string(pool)?));
+        }
+        return self
+            .run(move |conn| {
+                let transaction = conn.transaction()?;
+                for (address, record) in records.iter() {
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO pools (address, record) VALUES (?1, ?2)",
+                        params![address, record],
+                    )?;
+                }
+                transaction.commit()?;
+                return Ok(());
+            })
+            .await;
+    }
+
+    async fn put_opportunity(&self, opportunity: &OpportunityRecord) -> Result<(), StoreError> {
+        let (timestamp, asset_trade, asset_loan, buy_dex, sell_dex, record) = (
+            opportunity.timestamp as i64,
+            format!("{:?}", opportunity.asset_trade),
+            format!("{:?}", opportunity.asset_loan),
+            opportunity.buy_dex.clone(),
+            opportunity.sell_dex.clone(),
+            serde_json::to_string(opportunity)?,
+        );
+        return self
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT INTO opportunities (timestamp, asset_trade, asset_loan, buy_dex, sell_dex, record)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![timestamp, asset_trade, asset_loan, buy_dex, sell_dex, record],
+                )?;
+                return Ok(());
+            })
+            .await;
+    }
+
+    async fn get_opportunities(&self, filter: &HistoryFilter) -> Result<Vec<OpportunityRecord>, StoreError> {
+        return self.get_history("opportunities", filter).await;
+    }
+
+    async fn put_trade(&self, trade: &TradeRecord) -> Result<(), StoreError> {
+        let (timestamp, asset_trade, asset_loan, buy_dex, sell_dex, record) = (
+            trade.timestamp as i64,
+            format!("{:?}", trade.asset_trade),
+            format!("{:?}", trade.asset_loan),
+            trade.buy_dex.clone(),
+            trade.sell_dex.clone(),
+            serde_json::to_string(trade)?,
+        );
+        return self
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT INTO trades (timestamp, asset_trade, asset_loan, buy_dex, sell_dex, record)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![timestamp, asset_trade, asset_loan, buy_dex, sell_dex, record],
+                )?;
+                return Ok(());
+            })
+            .await;
+    }
+
+    async fn get_trades(&self, filter: &HistoryFilter) -> Result<Vec<TradeRecord>, StoreError> {
+        return self.get_history("trades", filter).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screening::TokenRisk;
+
+    #[tokio::test]
+    async fn migrate_assets_without_risks() {
+        let path = std::env::temp_dir().join(format!("arbitrageur-migration-{}.db", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let address = Address::repeat_byte(0x01);
+        {
+            // assets table as created before assets were screened
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE assets (address TEXT PRIMARY KEY, symbol TEXT NOT NULL, decimals INTEGER NOT NULL);",
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO assets (address, symbol, decimals) VALUES (?1, ?2, ?3)",
+                params![format!("{:?}", address), "WETH", 18],
+            )
+            .unwrap();
+        }
+
+        let store = SqliteStore::new(&path).await.unwrap();
+        let stored = store.get_assets(&vec![address]).await.unwrap().pop().flatten();
+        let screened = AssetRecord {
+            address,
+            symbol: String::from("WETH"),
+            decimals: 18,
+            risks: Some(vec![TokenRisk::Proxy]),
+        };
+        store.put_assets(&vec![screened]).await.unwrap();
+        let rescreened = store.get_assets(&vec![address]).await.unwrap().pop().flatten();
+        // migrations are only applied once
+        let reopened = SqliteStore::new(&path).await.map(|_| ());
+        std::fs::remove_file(&path).unwrap();
+
+        let stored = stored.unwrap();
+        assert_eq!((stored.symbol.as_str(), stored.decimals, stored.risks), ("WETH", 18, None));
+        assert_eq!(rescreened.unwrap().risks, Some(vec![TokenRisk::Proxy]));
+        assert!(reopened.is_ok());
+    }
+}
@@ -1,250 +1,248 @@
-use crate::assets::{Asset, Ierc20};
+#[cfg(test)]
+mod mock;
+mod quickswapv3;
+mod uniswapv2;
+mod uniswapv3;
+
+use crate::amounts::{Amount, Price};
+use crate::assets::Asset;
+use crate::multicall::{self, CallError, Multicall};
+use crate::pools::PoolKind;
+use async_trait::async_trait;
 use ethers::{
-    contract::abigen,
-    providers::{Http, Middleware, Provider},
-    types::{Address, Chain, U256},
-    utils::{format_units, parse_units},
+    types::{Address, U256},
+    utils::parse_units,
 };
-use std::sync::Arc;
-
-const UNISWAPV3_FEES_LENGTH: usize = 4 ;
-const UNISWAPV3_FEES: [u32; UNISWAPV3_FEES_LENGTH] = [100,500, 3000, 10000];
-
-// NOTE: How to query 
-// UniswapV2Pair::new(pair_address,Arc::new(&provider)) ;
-// let pair = pairFor(factory,token0,token1)
-// let (reserve0,reserve1) = pair.get_reserves(factory,token0,token1).call().await
-// let amount_out =  get_amount_out(amount_in,reserve0,reserve1)
-// let amount_out =  get_amounts_out(amount_in,path)
-
-abigen!(
-    UniswapV3Factory, "./data/abis/UniswapV3Factory.json";
-    UniswapV3Quoter, "./data/abis/UniswapV3Quoter.json" ;
-    QuickswapV3Factory,
-    r#"[
-        function poolByPair(address tokenA, address tokenB) external view returns (address pool)
-    ]"#;
-    QuickswapV3Quoter,
-    r#"[
-        function quoteExactInput(bytes memory path, uint256 amountIn) external returns (uint256 amountOut, uint16[] memory fees)
-        function quoteExactInputSingle(address tokenIn, address tokenOut, uint256 amountIn, uint160 limitSqrtPrice) external returns (uint256 amountOut, uint16 fee)
-    ]"#;
-    // Arbitrageur, "./data/abis/Arbitrageur.json";
-);
-
-enum UniswapV3Fee {
-    Lowest = 100,
-    Low = 500,
-    Medium = 3000,
-    High = 10_000,
-}
+use futures::future::join_all;
+use std::fmt;
+
+#[cfg(test)]
+pub use mock::MockDex;
+pub use quickswapv3::QuickswapV3;
+pub use uniswapv2::{get_amount_out, UniswapV2};
+pub use uniswapv3::UniswapV3;
 
 // NOTE: fees:
-// 0.1%-0.15% on quickswapV3 
-// 0.3% on uniswapv2,sushiswap,quickswapV2 
-#[derive(Clone)]
-pub enum Dex {
-    UniswapV3 {
-        name: String,
-        factory: Address,
-        quoter: Address,
-        provider: Arc<Provider<Http>>,
-    },
-    QuickswapV3 {
-        name: String,
-        factory: Address,
-        quoter: Address,
-        provider: Arc<Provider<Http>>,
-    },
-    // UniswapV2(Address),
-    // QuickswapV2(Address),
+// 0.1%-0.15% on quickswapV3
+// 0.3% on uniswapv2,sushiswap,quickswapV2
+
+/// error of a dex adapter
+#[derive(Clone, Copy, Debug)]
+pub enum DexError {
+    /// the pair has no pool in the dex
+    PoolNotFound,
+
+    /// quoting the swap reverted, e.g the pool has not enough liquidity
+    QuoteReverted,
+
+    /// rpc request failed
+    Rpc,
+
+    /// amount can not be represented with the decimals of its asset
+    DecimalsOverflow,
+}
+
+impl From<CallError> for DexError {
+    fn from(err: CallError) -> DexError {
+        match err {
+            CallError::Reverted => return DexError::QuoteReverted,
+            CallError::Rpc => return DexError::Rpc,
+        }
+    }
+}
+
+/// pool of a pair in a dex
+#[derive(Clone, Debug)]
+pub struct Pool {
+    pub address: Address,
+
+    /// fee tier of the pool on dexs with a pool per fee tier(uniswapV3)
+    pub fee_tier: Option<u32>,
 }
 
-impl Dex {
-    pub fn name(&self) -> String {
+/// denominator of fees, they are expressed in hundredths of a bip
+pub const FEE_DENOMINATOR: u32 = 1_000_000;
+
+/// side of a quote from the point of view of the base asset of a pair
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// swap the quote asset to the base asset
+    Buy,
+
+    /// swap the base asset to the quote asset
+    Sell,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::UniswapV3 {
-                name,
-                factory,
-                quoter,
-                provider,
-            } => return name.clone(),
-            Self::QuickswapV3 {
-                name,
-                factory,
-                quoter,
-                provider,
-            } => return name.clone(),
+            Side::Buy => return write!(f, "buy"),
+            Side::Sell => return write!(f, "sell"),
         }
     }
+}
+
+/// quote of swapping 1 unit of the spent asset in a dex pool, buy quotes spend
+/// the quote asset and sell quotes the base asset
+#[derive(Clone, Debug)]
+pub struct Quote {
+    pub side: Side,
+
+    /// amount of the spent asset
+    pub in_amount: Amount,
+
+    /// amount of the received asset
+    pub out_amount: Amount,
+
+    /// price of the base asset in the quote asset paid(buy) or received(sell)
+    pub price: Price,
+
+    /// quoted pool
+    pub pool: Pool,
+
+    /// fee paid to the pool by the quoted swap
+    pub fee: u32,
+}
+
+/// adapter to a dex protocol, implement it to make a dex
+/// available to watchers
+///
+/// fees are expressed in hundredths of a bip(1e-6) as in uniswapV3
+#[async_trait]
+pub trait Dex: Send + Sync {
+    fn name(&self) -> String;
+
+    /// implementation of the pools of the dex
+    fn pool_kind(&self) -> PoolKind;
+
+    /// batcher of the dex contracts calls, they are sent directly without it
+    fn multicall(&self) -> Option<&Multicall> {
+        return None;
+    }
 
-    pub async fn check_assets_price(
+    /// get pools of the pair `asset0`/`asset1` in the dex
+    async fn get_pools(&self, asset0: &Asset, asset1: &Asset) -> Result<Vec<Pool>, DexError>;
+
+    /// get output amount of `asset_out` for `amount_in` of `asset_in` in `pool`
+    async fn quote_exact_input(
         &self,
+        pool: &Pool,
         asset_in: &Asset,
         asset_out: &Asset,
-    ) -> Result<(f64, f64), ()> {
-        match self {
-            Self::UniswapV3 {
-                name,
-                factory,
-                quoter,
-                provider,
-            } => {
-                let (quoter, in_amount) = (
-                    UniswapV3Quoter::new(*quoter, provider.clone()),
-                    U256::from(parse_units(1, asset_in.decimals()).unwrap()),
-                );
-                let out_amount = quoter
-                    .quote_exact_input_single(
-                        asset_in.address(),
-                        asset_out.address(),
-                        UniswapV3Fee::Low as u32,
-                        in_amount,
-                        U256::zero(),
-                    )
-                    .call()
-                    .await
-                    .unwrap();
-                let out_amt: f64 = format_units(out_amount.clone(), asset_out.decimals())
-                    .unwrap()
-                    .parse()
-                    .unwrap();
-                let price = {
-                    let in_amt: f64 = format_units(in_amount, asset_in.decimals())
-                        .unwrap()
-                        .parse()
-                        .unwrap();
-                    in_amt / out_amt
-                };
-
-                return Ok((out_amt, price));
-            }
-            Self::QuickswapV3 {
-                name,
-                factory,
-                quoter,
-                provider,
-            } => {
-                let (quoter, in_amount) = (
-                    QuickswapV3Quoter::new(*quoter, provider.clone()),
-                    U256::from(parse_units(1, asset_in.decimals()).unwrap()),
-                );
-                let (out_amount, fee) = quoter
-                    .quote_exact_input_single(
-                        asset_in.address(),
-                        asset_out.address(),
-                        in_amount,
-                        U256::zero(),
-                    )
-                    .call()
-                    .await
-                    .unwrap();
-                let out_amt: f64 = format_units(out_amount.clone(), asset_out.decimals())
-                    .unwrap()
-                    .parse()
-                    .unwrap();
-                let price = {
-                    let in_amt: f64 = format_units(in_amount, asset_in.decimals())
-                        .unwrap()
-                        .parse()
-                        .unwrap();
-                    in_amt / out_amt
-                };
-
-                return Ok((out_amt, price));
-            }
+        amount_in: U256,
+    ) -> Result<U256, DexError>;
+
+    /// get output amount of `asset_out` for `amount_in` of `asset_in` in `pool`
+    /// along with the fee paid to the pool by the swap
+    async fn quote_exact_input_with_fee(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<(U256, u32), DexError> {
+        let amount_out = self.quote_exact_input(pool, asset_in, asset_out, amount_in).await?;
+        let fee = self.fee(pool).await?;
+        return Ok((amount_out, fee));
+    }
+
+    /// get output amount of swapping `amount_in` of the first asset of `path` through
+    /// `pools`, hop `idx` swaps `path[idx]` to `path[idx + 1]` in `pools[idx]`
+    async fn quote_exact_input_path(
+        &self,
+        pools: &Vec<Pool>,
+        path: &Vec<Asset>,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        if path.len() != pools.len() + 1 {
+            return Err(DexError::PoolNotFound);
+        }
+        let mut amount = amount_in;
+        for (idx, pool) in pools.iter().enumerate() {
+            amount = self.quote_exact_input(pool, &path[idx], &path[idx + 1], amount).await?;
         }
+        return Ok(amount);
     }
 
-    pub async fn get_pool_balance(&self, asset_trade: Asset, asset_loan: Asset) -> (f64, f64,Address) {
-        match self {
-            Self::UniswapV3 {
-                name,
-                factory,
-                quoter,
-                provider,
-            } => {
-                let pool_address = {
-                    let factory_contract = UniswapV3Factory::new(factory.clone(), provider.clone());
-                    let pool_address = factory_contract
-                        .get_pool(
-                            asset_trade.address(),
-                            asset_loan.address(),
-                            UniswapV3Fee::Low as u32,
-                        )
-                        .call()
-                        .await
-                        .unwrap();
-                    pool_address
-                };
-                let (asset_trade_balance, asset_loan_balance) = (
-                    asset_trade
-                        .contract
-                        .balance_of(pool_address.clone())
-                        .call()
-                        .await
-                        .unwrap(),
-                    asset_loan
-                        .contract
-                        .balance_of(pool_address.clone())
-                        .call()
-                        .await
-                        .unwrap(),
-                );
-                let (asset_trade_balance_out, asset_loan_balance_out) = (
-                    format_units(asset_trade_balance, asset_trade.decimals())
-                        .unwrap()
-                        .parse::<f64>()
-                        .unwrap(),
-                    format_units(asset_loan_balance, asset_loan.decimals())
-                        .unwrap()
-                        .parse::<f64>()
-                        .unwrap(),
-                );
-                return (asset_trade_balance_out, asset_loan_balance_out,pool_address);
-            }
-            Self::QuickswapV3 {
-                name,
-                factory,
-                quoter,
-                provider,
-            } => {
-                let pool_address = {
-                    let factory_contract =
-                        QuickswapV3Factory::new(factory.clone(), provider.clone());
-                    let pool_address = factory_contract
-                        .pool_by_pair(asset_trade.address(), asset_loan.address())
-                        .call()
-                        .await
-                        .unwrap();
-                    pool_address
-                };
-                let (asset_trade_balance, asset_loan_balance) = (
-                    asset_trade
-                        .contract
-                        .balance_of(pool_address.clone())
-                        .call()
-                        .await
-                        .unwrap(),
-                    asset_loan
-                        .contract
-                        .balance_of(pool_address.clone())
-                        .call()
-                        .await
-                        .unwrap(),
-                );
-                let (asset_trade_balance_out, asset_loan_balance_out) = (
-                    format_units(asset_trade_balance, asset_trade.decimals())
-                        .unwrap()
-                        .parse::<f64>()
-                        .unwrap(),
-                    format_units(asset_loan_balance, asset_loan.decimals())
-                        .unwrap()
-                        .parse::<f64>()
-                        .unwrap(),
-                );
-                return (asset_trade_balance_out, asset_loan_balance_out, pool_address);
+    /// get balances of `asset0` and `asset1` held by `pool`
+    async fn get_pool_liquidity(
+        &self,
+        pool: &Pool,
+        asset0: &Asset,
+        asset1: &Asset,
+    ) -> Result<(U256, U256), DexError> {
+        let balances = futures::try_join!(
+            multicall::call(self.multicall(), asset0.contract.balance_of(pool.address)),
+            multicall::call(self.multicall(), asset1.contract.balance_of(pool.address))
+        )?;
+        return Ok(balances);
+    }
+
+    /// get swap fee charged by `pool`
+    async fn fee(&self, pool: &Pool) -> Result<u32, DexError>;
+
+    /// quote the `side` of the `asset_base`/`asset_quote` pair swapping 1 unit of the spent
+    /// asset in every pool of the dex, pools are quoted concurrently and the ones that
+    /// fail are skipped
+    async fn check_assets_prices(&self, asset_base: &Asset, asset_quote: &Asset, side: Side) -> Result<Vec<Quote>, DexError> {
+        let (asset_in, asset_out) = match side {
+            Side::Buy => (asset_quote, asset_base),
+            Side::Sell => (asset_base, asset_quote),
+        };
+        let pools = self.get_pools(asset_in, asset_out).await?;
+        if pools.is_empty() {
+            return Err(DexError::PoolNotFound);
+        }
+
+        let in_amount = Amount::one(asset_in.decimals())?;
+
+        let pools_quotes = join_all(
+            pools
+                .iter()
+                .map(|pool| self.quote_exact_input_with_fee(pool, asset_in, asset_out, in_amount.raw)),
+        )
+        .await;
+
+        let mut quotes = Vec::<Quote>::with_capacity(pools.len());
+        let mut error = DexError::QuoteReverted;
+        for (pool, pool_quote) in pools.iter().zip(pools_quotes) {
+            let (out_amount, fee) = match pool_quote {
+                Ok(pool_quote) => pool_quote,
+                Err(err) => {
+                    error = err;
+                    continue;
+                }
+            };
+            if out_amount.is_zero() {
+                continue;
             }
+            let out_amount = Amount::new(out_amount, asset_out.decimals());
+            let price = match side {
+                Side::Buy => Price::new(out_amount, in_amount),
+                Side::Sell => Price::new(in_amount, out_amount),
+            };
+            quotes.push(Quote {
+                side,
+                in_amount,
+                out_amount,
+                price,
+                pool: pool.clone(),
+                fee,
+            });
         }
+
+        if quotes.is_empty() {
+            return Err(error);
+        }
+        return Ok(quotes);
     }
 }
+
+/// `amount` units of an asset with `decimals` to its smallest unit, `amount`
+/// is a decimal string so it's parsed exactly
+pub fn from_decimal(amount: &str, decimals: u32) -> Result<U256, DexError> {
+    let Ok(amount) = parse_units(amount, decimals) else {
+        return Err(DexError::DecimalsOverflow)
+    };
+    return Ok(U256::from(amount));
+}
@@ -0,0 +1,73 @@
+use super::{Dex, DexError, Pool};
+use crate::assets::Asset;
+use crate::pools::PoolKind;
+use async_trait::async_trait;
+use ethers::types::{Address, U256};
+use std::collections::HashMap;
+
+/// dex quoting swaps at fixed rates, meant for tests
+pub struct MockDex {
+    name: String,
+    pools: Vec<Pool>,
+
+    /// output amount of 1 unit of an asset swapped in a pool, by pool and spent asset
+    rates: HashMap<(Address, Address), (U256, u32)>,
+
+    fee: u32,
+}
+
+impl MockDex {
+    pub fn new(name: &str, fee: u32) -> MockDex {
+        MockDex {
+            name: String::from(name),
+            pools: vec![],
+            rates: HashMap::new(),
+            fee,
+        }
+    }
+
+    /// quote `out_per_unit` of the received asset for every unit of `asset_in`
+    /// swapped in the pool at `pool`
+    pub fn with_rate(mut self, pool: Address, asset_in: &Asset, out_per_unit: U256) -> MockDex {
+        if !self.pools.iter().any(|known_pool| known_pool.address == pool) {
+            self.pools.push(Pool {
+                address: pool,
+                fee_tier: None,
+            });
+        }
+        self.rates.insert((pool, asset_in.address), (out_per_unit, asset_in.decimals));
+        return self;
+    }
+}
+
+#[async_trait]
+impl Dex for MockDex {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn pool_kind(&self) -> PoolKind {
+        return PoolKind::UniswapV2;
+    }
+
+    async fn get_pools(&self, _asset0: &Asset, _asset1: &Asset) -> Result<Vec<Pool>, DexError> {
+        return Ok(self.pools.clone());
+    }
+
+    async fn quote_exact_input(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        _asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        let Some((out_per_unit, decimals)) = self.rates.get(&(pool.address, asset_in.address)) else {
+            return Err(DexError::PoolNotFound)
+        };
+        return Ok(amount_in * out_per_unit / U256::exp10(*decimals as usize));
+    }
+
+    async fn fee(&self, _pool: &Pool) -> Result<u32, DexError> {
+        return Ok(self.fee);
+    }
+}
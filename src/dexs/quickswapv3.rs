@@ -0,0 +1,142 @@
+use super::{Dex, DexError, Pool};
+use crate::assets::Asset;
+use crate::context::Context;
+use crate::contracts::{AlgebraPool, QuickswapV3Factory, QuickswapV3Quoter};
+use crate::multicall::{self, Multicall};
+use crate::pools::PoolKind;
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Provider},
+    types::{Address, Bytes, U256},
+};
+use std::sync::Arc;
+
+/// quickswapV3 is an algebra dex, it has a single pool per pair
+/// with a dynamic fee
+pub struct QuickswapV3 {
+    name: String,
+    factory: QuickswapV3Factory<Provider<Http>>,
+    quoter: QuickswapV3Quoter<Provider<Http>>,
+    context: Arc<Context>,
+}
+
+impl QuickswapV3 {
+    pub fn new(name: String, factory: Address, quoter: Address, context: Arc<Context>) -> QuickswapV3 {
+        QuickswapV3 {
+            name,
+            factory: QuickswapV3Factory::new(factory, context.provider.clone()),
+            quoter: QuickswapV3Quoter::new(quoter, context.provider.clone()),
+            context,
+        }
+    }
+
+    /// quote off-chain with the local state of `pool` and its current fee
+    fn quote_exact_input_offchain(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Option<(U256, u32)> {
+        let pools_state = self.context.pools_state.as_ref()?;
+        let amount_out = pools_state.quote_exact_input(pool.address, asset_in.address(), asset_out.address(), amount_in)?;
+        let fee = pools_state.fee(pool.address)?;
+        return Some((amount_out, fee));
+    }
+}
+
+#[async_trait]
+impl Dex for QuickswapV3 {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn pool_kind(&self) -> PoolKind {
+        return PoolKind::Algebra;
+    }
+
+    fn multicall(&self) -> Option<&Multicall> {
+        return self.context.multicall();
+    }
+
+    async fn get_pools(&self, asset0: &Asset, asset1: &Asset) -> Result<Vec<Pool>, DexError> {
+        if let Some(registry) = &self.context.registry {
+            return Ok(registry.get_pools(self.factory.address(), asset0.address(), asset1.address()));
+        }
+        let pool_address = multicall::call(self.multicall(), self.factory.pool_by_pair(asset0.address(), asset1.address())).await?;
+        if pool_address == Address::zero() {
+            return Ok(vec![]);
+        }
+        return Ok(vec![Pool {
+            address: pool_address,
+            fee_tier: None,
+        }]);
+    }
+
+    async fn quote_exact_input(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        if let Some((amount_out, _)) = self.quote_exact_input_offchain(pool, asset_in, asset_out, amount_in) {
+            return Ok(amount_out);
+        }
+        let (amount_out, _) = multicall::call(
+            self.multicall(),
+            self.quoter.quote_exact_input_single(asset_in.address(), asset_out.address(), amount_in, U256::zero()),
+        )
+        .await?;
+        return Ok(amount_out);
+    }
+
+    /// quoter returns the dynamic fee charged by the pool for the swap
+    async fn quote_exact_input_with_fee(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<(U256, u32), DexError> {
+        if let Some(quote) = self.quote_exact_input_offchain(pool, asset_in, asset_out, amount_in) {
+            return Ok(quote);
+        }
+        let (amount_out, fee) = multicall::call(
+            self.multicall(),
+            self.quoter.quote_exact_input_single(asset_in.address(), asset_out.address(), amount_in, U256::zero()),
+        )
+        .await?;
+        return Ok((amount_out, fee as u32));
+    }
+
+    /// quote the whole path in a single quoter call, algebra paths
+    /// are the addresses of the assets concatenated
+    async fn quote_exact_input_path(
+        &self,
+        pools: &Vec<Pool>,
+        path: &Vec<Asset>,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        if path.len() != pools.len() + 1 {
+            return Err(DexError::PoolNotFound);
+        }
+        let encoded_path: Vec<u8> = path
+            .iter()
+            .flat_map(|asset| asset.address().as_bytes().to_vec())
+            .collect();
+        let (amount_out, _) = multicall::call(
+            self.multicall(),
+            self.quoter.quote_exact_input(Bytes::from(encoded_path), amount_in),
+        )
+        .await?;
+        return Ok(amount_out);
+    }
+
+    /// current dynamic fee of the pool
+    async fn fee(&self, pool: &Pool) -> Result<u32, DexError> {
+        let pool_contract = AlgebraPool::new(pool.address, self.context.provider.clone());
+        let (_, _, fee, _, _, _, _) = multicall::call(self.multicall(), pool_contract.global_state()).await?;
+        return Ok(fee as u32);
+    }
+}
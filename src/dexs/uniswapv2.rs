@@ -0,0 +1,112 @@
+use super::{Dex, DexError, Pool};
+use crate::assets::Asset;
+use crate::contracts::{UniswapV2Factory, UniswapV2Pair};
+use crate::context::Context;
+use crate::multicall::{self, Multicall};
+use crate::pools::PoolKind;
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Provider},
+    types::{Address, U256},
+};
+use std::sync::Arc;
+
+// 0.3% fee charged by uniswapV2 clones, expressed as in UniswapV2Library.getAmountOut
+const UNISWAPV2_FEE: u32 = 3000;
+const UNISWAPV2_FEE_NUMERATOR: u32 = 997;
+const UNISWAPV2_FEE_DENOMINATOR: u32 = 1000;
+
+/// uniswapV2 and its clones(quickswapV2,sushiswap), a single pool(pair) per pair
+pub struct UniswapV2 {
+    name: String,
+    factory: UniswapV2Factory<Provider<Http>>,
+    context: Arc<Context>,
+}
+
+impl UniswapV2 {
+    pub fn new(name: String, factory: Address, context: Arc<Context>) -> UniswapV2 {
+        UniswapV2 {
+            name,
+            factory: UniswapV2Factory::new(factory, context.provider.clone()),
+            context,
+        }
+    }
+
+    /// get reserves of `asset_in` and `asset_out` in the pair `pool`,
+    /// ordered as (reserve_in,reserve_out)
+    async fn get_reserves(&self, pool: &Pool, asset_in: &Asset, asset_out: &Asset) -> Result<(U256, U256), DexError> {
+        let pair = UniswapV2Pair::new(pool.address, self.context.provider.clone());
+        let (reserve0, reserve1, _) = multicall::call(self.multicall(), pair.get_reserves()).await?;
+
+        // pairs sort their tokens by address, token0 < token1
+        if asset_in.address() < asset_out.address() {
+            return Ok((U256::from(reserve0), U256::from(reserve1)));
+        } else {
+            return Ok((U256::from(reserve1), U256::from(reserve0)));
+        }
+    }
+}
+
+#[async_trait]
+impl Dex for UniswapV2 {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn pool_kind(&self) -> PoolKind {
+        return PoolKind::UniswapV2;
+    }
+
+    fn multicall(&self) -> Option<&Multicall> {
+        return self.context.multicall();
+    }
+
+    async fn get_pools(&self, asset0: &Asset, asset1: &Asset) -> Result<Vec<Pool>, DexError> {
+        if let Some(registry) = &self.context.registry {
+            return Ok(registry.get_pools(self.factory.address(), asset0.address(), asset1.address()));
+        }
+        let pair_address = multicall::call(self.multicall(), self.factory.get_pair(asset0.address(), asset1.address())).await?;
+        if pair_address == Address::zero() {
+            return Ok(vec![]);
+        }
+        return Ok(vec![Pool {
+            address: pair_address,
+            fee_tier: None,
+        }]);
+    }
+
+    async fn quote_exact_input(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        let local_amount_out = self
+            .context
+            .pools_state
+            .as_ref()
+            .and_then(|pools_state| pools_state.quote_exact_input(pool.address, asset_in.address(), asset_out.address(), amount_in));
+        if let Some(amount_out) = local_amount_out {
+            return Ok(amount_out);
+        }
+        let (reserve_in, reserve_out) = self.get_reserves(pool, asset_in, asset_out).await?;
+        return Ok(get_amount_out(amount_in, reserve_in, reserve_out));
+    }
+
+    async fn fee(&self, _pool: &Pool) -> Result<u32, DexError> {
+        return Ok(UNISWAPV2_FEE);
+    }
+}
+
+/// off-chain version of UniswapV2Library.getAmountOut, given an input amount of an
+/// asset and pair reserves, returns the maximum output amount of the other asset
+pub fn get_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
+    if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+        return U256::zero();
+    }
+    let amount_in_with_fee = amount_in * UNISWAPV2_FEE_NUMERATOR;
+    let numerator = amount_in_with_fee * reserve_out;
+    let denominator = reserve_in * UNISWAPV2_FEE_DENOMINATOR + amount_in_with_fee;
+    return numerator / denominator;
+}
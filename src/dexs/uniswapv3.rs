@@ -0,0 +1,174 @@
+use super::{Dex, DexError, Pool};
+use crate::assets::Asset;
+use crate::context::Context;
+use crate::contracts::{UniswapV3Factory, UniswapV3Quoter};
+use crate::multicall::{self, Multicall};
+use crate::pools::PoolKind;
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Provider},
+    types::{Address, Bytes, U256},
+};
+use futures::future::join_all;
+use std::sync::Arc;
+
+const UNISWAPV3_FEES_LENGTH: usize = 4 ;
+const UNISWAPV3_FEES: [u32; UNISWAPV3_FEES_LENGTH] = [100,500, 3000, 10000];
+
+pub struct UniswapV3 {
+    name: String,
+    factory: UniswapV3Factory<Provider<Http>>,
+    quoter: UniswapV3Quoter<Provider<Http>>,
+    context: Arc<Context>,
+
+    /// compare off-chain quotes with the quoter ones, logging mismatches
+    cross_check: bool,
+}
+
+impl UniswapV3 {
+    pub fn new(name: String, factory: Address, quoter: Address, context: Arc<Context>) -> UniswapV3 {
+        UniswapV3 {
+            name,
+            factory: UniswapV3Factory::new(factory, context.provider.clone()),
+            quoter: UniswapV3Quoter::new(quoter, context.provider.clone()),
+            context,
+            cross_check: false,
+        }
+    }
+
+    pub fn with_cross_check(mut self, cross_check: bool) -> UniswapV3 {
+        self.cross_check = cross_check;
+        return self;
+    }
+
+    async fn quote_exact_input_onchain(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        let amount_out = multicall::call(
+            self.multicall(),
+            self.quoter.quote_exact_input_single(
+                asset_in.address(),
+                asset_out.address(),
+                Self::get_fee_tier(pool)?,
+                amount_in,
+                U256::zero(),
+            ),
+        )
+        .await?;
+        return Ok(amount_out);
+    }
+
+    #[inline]
+    fn get_fee_tier(pool: &Pool) -> Result<u32, DexError> {
+        let Some(fee_tier) = pool.fee_tier else {
+            return Err(DexError::PoolNotFound)
+        };
+        return Ok(fee_tier);
+    }
+}
+
+#[async_trait]
+impl Dex for UniswapV3 {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn pool_kind(&self) -> PoolKind {
+        return PoolKind::UniswapV3;
+    }
+
+    fn multicall(&self) -> Option<&Multicall> {
+        return self.context.multicall();
+    }
+
+    /// get pools of the pair from the registry or, without it, one per fee tier
+    /// in `UNISWAPV3_FEES` that has been created in the factory, looked up concurrently
+    async fn get_pools(&self, asset0: &Asset, asset1: &Asset) -> Result<Vec<Pool>, DexError> {
+        if let Some(registry) = &self.context.registry {
+            return Ok(registry.get_pools(self.factory.address(), asset0.address(), asset1.address()));
+        }
+        let pools_addresses = join_all(UNISWAPV3_FEES.map(|fee| {
+            multicall::call(self.multicall(), self.factory.get_pool(asset0.address(), asset1.address(), fee))
+        }))
+        .await;
+
+        let mut pools = Vec::<Pool>::with_capacity(UNISWAPV3_FEES_LENGTH);
+        for (fee, pool_address) in UNISWAPV3_FEES.into_iter().zip(pools_addresses) {
+            let Ok(pool_address) = pool_address else {
+                continue
+            };
+            if pool_address != Address::zero() {
+                pools.push(Pool {
+                    address: pool_address,
+                    fee_tier: Some(fee),
+                });
+            }
+        }
+        return Ok(pools);
+    }
+
+    /// quote off-chain when the pool state is synced locally
+    async fn quote_exact_input(
+        &self,
+        pool: &Pool,
+        asset_in: &Asset,
+        asset_out: &Asset,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        let local_amount_out = self
+            .context
+            .pools_state
+            .as_ref()
+            .and_then(|pools_state| pools_state.quote_exact_input(pool.address, asset_in.address(), asset_out.address(), amount_in));
+        let Some(local_amount_out) = local_amount_out else {
+            return self.quote_exact_input_onchain(pool, asset_in, asset_out, amount_in).await
+        };
+        if !self.cross_check {
+            return Ok(local_amount_out);
+        }
+
+        let amount_out = self.quote_exact_input_onchain(pool, asset_in, asset_out, amount_in).await?;
+        if amount_out != local_amount_out {
+            println!(
+                "{} pool {:?} off-chain quote mismatch: {local_amount_out} != {amount_out}",
+                self.name, pool.address
+            );
+        }
+        return Ok(amount_out);
+    }
+
+    /// quote the whole path in a single quoter call, uniswapV3 paths are the
+    /// addresses of the assets with the 3 bytes fee of each hop pool between them
+    async fn quote_exact_input_path(
+        &self,
+        pools: &Vec<Pool>,
+        path: &Vec<Asset>,
+        amount_in: U256,
+    ) -> Result<U256, DexError> {
+        if path.len() != pools.len() + 1 {
+            return Err(DexError::PoolNotFound);
+        }
+        let mut encoded_path = Vec::<u8>::with_capacity(path.len() * 20 + pools.len() * 3);
+        for (idx, asset) in path.iter().enumerate() {
+            encoded_path.extend_from_slice(asset.address().as_bytes());
+            if let Some(pool) = pools.get(idx) {
+                let fee_tier = Self::get_fee_tier(pool)?;
+                encoded_path.extend_from_slice(&fee_tier.to_be_bytes()[1..]);
+            }
+        }
+        let amount_out = multicall::call(
+            self.multicall(),
+            self.quoter.quote_exact_input(Bytes::from(encoded_path), amount_in),
+        )
+        .await?;
+        return Ok(amount_out);
+    }
+
+    async fn fee(&self, pool: &Pool) -> Result<u32, DexError> {
+        return Self::get_fee_tier(pool);
+    }
+}
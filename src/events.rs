@@ -1,67 +1,102 @@
 use ethers::{
-    contract::abigen,
-    core::types::Address,
-    providers::{Provider, StreamExt, Ws},
+    providers::{Middleware, Provider, StreamExt, Ws},
+    types::{Address, Filter, Log, H256},
+    utils::keccak256,
 };
-use eyre::Result;
-use std::sync::Arc;
+use std::time;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-abigen!(
-    IERC20,
-    r#"[
-        event Transfer(address indexed from, address indexed to, uint256 value)
-        event Approval(address indexed owner, address indexed spender, uint256 value)
-    ]"#,
-);
+// milliseconds without new logs after which the logs of a block are flushed
+const LOGS_DEBOUNCE: u64 = 100;
 
-const WSS_URL: &str = "wss://mainnet.infura.io/ws/v3/c60b0bb42f8a4c6481ecd229eddaca27";
-const WETH_ADDRESS: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+// events that change the state of uniswapV3/algebra pools and uniswapV2 pairs,
+// algebra pools events have the same signatures as uniswapV3 ones plus the
+// dynamic fee changes
+const POOLS_EVENTS_SIGNATURES: [&str; 8] = [
+    "Swap(address,address,int256,int256,uint160,uint128,int24)",
+    "Mint(address,address,int24,int24,uint128,uint256,uint256)",
+    "Burn(address,int24,int24,uint128,uint256,uint256)",
+    "Fee(uint16)",
+    "Swap(address,uint256,uint256,uint256,uint256,address)",
+    "Sync(uint112,uint112)",
+    "Mint(address,uint256,uint256)",
+    "Burn(address,uint256,uint256,address)",
+];
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let provider = Provider::<Ws>::connect(WSS_URL).await?;
-    let client = Arc::new(provider);
-    let address: Address = WETH_ADDRESS.parse()?;
-    let contract = IERC20::new(address, client);
+/// connect to `ws_url` and subscribe to the state changing events of `pools`,
+/// the events are sent through the returned receiver grouped by block as soon
+/// as the block ones are received, it's closed when the socket drops
+pub async fn listen(ws_url: String, pools: Vec<Address>) -> Result<UnboundedReceiver<Vec<Log>>, ()> {
+    let Ok(provider) = Provider::<Ws>::connect(ws_url).await else {
+        println!("error connecting to websocket");
+        return Err(())
+    };
 
-    listen_all_events(&contract).await?;
-    listen_specific_events(&contract).await?;
+    let (sender, receiver) = mpsc::unbounded_channel::<Vec<Log>>();
+    tokio::spawn(async move {
+        if let Err(_) = forward_events(&provider, pools, &sender).await {
+            println!("error subscribing to events");
+        }
+    });
 
-    Ok(())
+    return Ok(receiver);
 }
 
-/// Given a contract instance, subscribe to all possible events.
-/// This allows to centralize the event handling logic and dispatch
-/// proper actions.
-///
-/// Note that all event bindings have been generated
-/// by abigen. Feel free to investigate the abigen expanded code to
-/// better understand types and functionalities.
-async fn listen_all_events(contract: &IERC20<Provider<Ws>>) -> Result<()> {
-    let events = contract.events().from_block(16232696);
-    let mut stream = events.stream().await?.take(1);
+async fn forward_events(
+    provider: &Provider<Ws>,
+    pools: Vec<Address>,
+    sender: &UnboundedSender<Vec<Log>>,
+) -> Result<(), ()> {
+    let topics: Vec<H256> = POOLS_EVENTS_SIGNATURES
+        .iter()
+        .map(|signature| H256::from(keccak256(signature)))
+        .collect();
+    let filter = Filter::new().address(pools).topic0(topics);
 
-    while let Some(Ok(evt)) = stream.next().await {
-        match evt {
-            IERC20Events::ApprovalFilter(f) => println!("{f:?}"),
-            IERC20Events::TransferFilter(f) => println!("{f:?}"),
-        }
-    }
+    let Ok(mut logs) = provider.subscribe_logs(&filter).await else {
+        return Err(())
+    };
 
-    Ok(())
-}
+    // logs of a block are flushed as soon as a log of another block arrives,
+    // or once no log has arrived for the debounce window after the last one
+    let mut next_log: Option<Log> = None;
+    loop {
+        let first_log = match next_log.take() {
+            Some(log) => log,
+            None => {
+                let Some(log) = logs.next().await else {
+                    return Ok(())
+                };
+                log
+            }
+        };
+        let block_number = first_log.block_number;
+        let mut block_logs = vec![first_log];
 
-/// Given a contract instance subscribe to a single type of event.
-/// Note that all event bindings have been generated
-/// by abigen. Feel free to investigate the abigen expanded code to
-/// better understand types and functionalities.
-async fn listen_specific_events(contract: &IERC20<Provider<Ws>>) -> Result<()> {
-    let events = contract.event::<ApprovalFilter>().from_block(16232696);
-    let mut stream = events.stream().await?.take(1);
+        let mut closed = false;
+        loop {
+            let debounce = tokio::time::sleep(time::Duration::from_millis(LOGS_DEBOUNCE));
+            tokio::select! {
+                log = logs.next() => {
+                    let Some(log) = log else {
+                        closed = true;
+                        break
+                    };
+                    if log.block_number != block_number {
+                        next_log = Some(log);
+                        break;
+                    }
+                    block_logs.push(log);
+                }
+                _ = debounce => break,
+            }
+        }
 
-    while let Some(Ok(f)) = stream.next().await {
-        println!("ApprovalFilter event: {f:?}");
+        if let Err(_) = sender.send(block_logs) {
+            return Ok(());
+        }
+        if closed {
+            return Ok(());
+        }
     }
-
-    Ok(())
 }
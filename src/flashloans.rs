@@ -0,0 +1,116 @@
+mod aavev3;
+mod balancer;
+mod uniswapv3;
+
+use crate::assets::Asset;
+use crate::multicall::CallError;
+use async_trait::async_trait;
+use ethers::types::{Address, U256};
+use futures::future::join_all;
+use std::{fmt, sync::Arc};
+
+pub use aavev3::AaveV3;
+pub use balancer::Balancer;
+pub use uniswapv3::UniswapV3Flash;
+
+/// error getting the offers of a flash loan provider
+#[derive(Clone, Copy, Debug)]
+pub enum FlashLoanError {
+    /// a call to the lender reverted
+    Reverted,
+
+    /// rpc request failed
+    Rpc,
+
+    /// the fee of the lender does not fit in the fees units
+    InvalidFee,
+}
+
+impl fmt::Display for FlashLoanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlashLoanError::Reverted => return write!(f, "lender call reverted"),
+            FlashLoanError::Rpc => return write!(f, "rpc error"),
+            FlashLoanError::InvalidFee => return write!(f, "invalid lender fee"),
+        }
+    }
+}
+
+impl From<CallError> for FlashLoanError {
+    fn from(err: CallError) -> FlashLoanError {
+        match err {
+            CallError::Reverted => return FlashLoanError::Reverted,
+            CallError::Rpc => return FlashLoanError::Rpc,
+        }
+    }
+}
+
+/// flash loan protocol, it defines the callback the arbitrageur
+/// contract implements to borrow from a lender
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlashLoanKind {
+    AaveV3 = 0,
+    Balancer = 1,
+    UniswapV3 = 2,
+}
+
+/// offer of a lender to flash borrow an asset
+#[derive(Clone, Debug)]
+pub struct FlashLoan {
+    pub kind: FlashLoanKind,
+
+    /// contract the asset is borrowed from, pool or vault
+    pub lender: Address,
+
+    /// fee charged on the amount borrowed
+    pub fee: u32,
+
+    /// amount of the asset available to borrow
+    pub liquidity: U256,
+}
+
+/// adapter to a flash loan protocol, implement it to make
+/// its lenders available to watchers
+///
+/// fees are expressed in hundredths of a bip(1e-6) as in dexs
+#[async_trait]
+pub trait FlashLoanProvider: Send + Sync {
+    fn name(&self) -> String;
+
+    /// get offers of the provider lenders to borrow `asset`, `excluded` contracts
+    /// can not lend it(e.g pools traded in the same transaction are locked)
+    async fn get_flash_loans(&self, asset: &Asset, excluded: &Vec<Address>) -> Result<Vec<FlashLoan>, FlashLoanError>;
+}
+
+/// get offers of every provider to borrow `asset`, providers are asked
+/// concurrently and the ones that fail are skipped
+pub async fn get_flash_loans(
+    providers: &Vec<Arc<dyn FlashLoanProvider>>,
+    asset: &Asset,
+    excluded: &Vec<Address>,
+) -> Vec<FlashLoan> {
+    let providers_flash_loans = join_all(
+        providers
+            .iter()
+            .map(|provider| provider.get_flash_loans(asset, excluded)),
+    )
+    .await;
+
+    let mut flash_loans = Vec::<FlashLoan>::new();
+    for (provider, provider_flash_loans) in providers.iter().zip(providers_flash_loans) {
+        match provider_flash_loans {
+            Ok(provider_flash_loans) => flash_loans.extend(provider_flash_loans),
+            Err(err) => println!("could not get {} flash loans: {err}", provider.name()),
+        }
+    }
+    return flash_loans;
+}
+
+/// offer with the lowest fee able to lend `amount`
+pub fn cheapest(flash_loans: &Vec<FlashLoan>, amount: U256) -> Option<FlashLoan> {
+    return flash_loans
+        .iter()
+        .filter(|flash_loan| flash_loan.liquidity >= amount)
+        .min_by_key(|flash_loan| flash_loan.fee)
+        .cloned();
+}
@@ -0,0 +1,90 @@
+use super::{FlashLoan, FlashLoanError, FlashLoanKind, FlashLoanProvider};
+use crate::assets::Asset;
+use crate::context::Context;
+use crate::multicall;
+use async_trait::async_trait;
+use ethers::{
+    contract::abigen,
+    providers::{Http, Provider},
+    types::Address,
+};
+use std::sync::Arc;
+
+// bits of the reserve configuration checked by the pool before lending, the
+// configuration is a struct of a single word so it's declared as the word
+const ACTIVE_BIT: usize = 56;
+const PAUSED_BIT: usize = 60;
+const FLASH_LOAN_ENABLED_BIT: usize = 63;
+
+// premium is expressed in bips
+const BIP: u32 = 100;
+
+abigen!(
+    AaveV3Pool,
+    r#"[
+        struct ReserveData { uint256 configuration; uint128 liquidityIndex; uint128 currentLiquidityRate; uint128 variableBorrowIndex; uint128 currentVariableBorrowRate; uint128 currentStableBorrowRate; uint40 lastUpdateTimestamp; uint16 id; address aTokenAddress; address stableDebtTokenAddress; address variableDebtTokenAddress; address interestRateStrategyAddress; uint128 accruedToTreasury; uint128 unbacked; uint128 isolationModeTotalDebt; }
+        function getReserveData(address asset) external view returns (ReserveData memory)
+        function FLASHLOAN_PREMIUM_TOTAL() external view returns (uint128)
+    ]"#;
+);
+
+/// aave V3 pool, it lends the reserves deposited in it for a premium
+/// set by the governance
+pub struct AaveV3 {
+    name: String,
+    pool: AaveV3Pool<Provider<Http>>,
+    context: Arc<Context>,
+}
+
+impl AaveV3 {
+    pub fn new(name: String, pool: Address, context: Arc<Context>) -> AaveV3 {
+        AaveV3 {
+            name,
+            pool: AaveV3Pool::new(pool, context.provider.clone()),
+            context,
+        }
+    }
+}
+
+#[async_trait]
+impl FlashLoanProvider for AaveV3 {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    /// the reserve of `asset` is lent from the pool, its available liquidity
+    /// is the balance of the reserve aToken
+    async fn get_flash_loans(&self, asset: &Asset, excluded: &Vec<Address>) -> Result<Vec<FlashLoan>, FlashLoanError> {
+        if excluded.contains(&self.pool.address()) {
+            return Ok(vec![]);
+        }
+
+        let (reserve_data, premium) = futures::try_join!(
+            multicall::call(self.context.multicall(), self.pool.get_reserve_data(asset.address())),
+            multicall::call(self.context.multicall(), self.pool.flashloan_premium_total())
+        )?;
+        let (configuration, _, _, _, _, _, _, _, a_token_address, _, _, _, _, _, _) = reserve_data;
+        let lendable = configuration.bit(ACTIVE_BIT)
+            && !configuration.bit(PAUSED_BIT)
+            && configuration.bit(FLASH_LOAN_ENABLED_BIT);
+        if a_token_address == Address::zero() || !lendable {
+            return Ok(vec![]);
+        }
+
+        let liquidity = multicall::call(
+            self.context.multicall(),
+            asset.contract.balance_of(a_token_address),
+        )
+        .await?;
+        let Ok(premium) = u32::try_from(premium) else {
+            return Err(FlashLoanError::InvalidFee)
+        };
+
+        return Ok(vec![FlashLoan {
+            kind: FlashLoanKind::AaveV3,
+            lender: self.pool.address(),
+            fee: premium * BIP,
+            liquidity,
+        }]);
+    }
+}
@@ -0,0 +1,93 @@
+use super::{FlashLoan, FlashLoanError, FlashLoanKind, FlashLoanProvider};
+use crate::assets::Asset;
+use crate::context::Context;
+use crate::multicall;
+use async_trait::async_trait;
+use ethers::{
+    contract::abigen,
+    providers::{Http, Provider},
+    types::{Address, U256},
+};
+use std::sync::Arc;
+
+// flash loan fee percentage is a fixed point number with 18 decimals,
+// fees are expressed with 6 of them
+const FEE_PERCENTAGE_TO_FEE: u64 = 1_000_000_000_000;
+
+abigen!(
+    BalancerVault,
+    r#"[
+        function getProtocolFeesCollector() external view returns (address)
+    ]"#;
+    ProtocolFeesCollector,
+    r#"[
+        function getFlashLoanFeePercentage() external view returns (uint256)
+    ]"#;
+);
+
+/// balancer vault, it lends the tokens of all the balancer pools it holds for
+/// the flash loan fee set by the governance in its protocol fees collector
+pub struct Balancer {
+    name: String,
+    vault: BalancerVault<Provider<Http>>,
+    context: Arc<Context>,
+}
+
+impl Balancer {
+    pub fn new(name: String, vault: Address, context: Arc<Context>) -> Balancer {
+        Balancer {
+            name,
+            vault: BalancerVault::new(vault, context.provider.clone()),
+            context,
+        }
+    }
+
+    /// fee of the vault flash loans, read from its current protocol fees collector
+    async fn get_fee(&self) -> Result<u32, FlashLoanError> {
+        let collector = multicall::call(self.context.multicall(), self.vault.get_protocol_fees_collector()).await?;
+        let collector = ProtocolFeesCollector::new(collector, self.context.provider.clone());
+        let fee_percentage = multicall::call(self.context.multicall(), collector.get_flash_loan_fee_percentage()).await?;
+
+        // rounded up so the fee is never underestimated
+        let (mut fee, remainder) = fee_percentage.div_mod(U256::from(FEE_PERCENTAGE_TO_FEE));
+        if !remainder.is_zero() {
+            fee += U256::one();
+        }
+        if fee > U256::from(u32::MAX) {
+            return Err(FlashLoanError::InvalidFee);
+        }
+        return Ok(fee.as_u32());
+    }
+}
+
+#[async_trait]
+impl FlashLoanProvider for Balancer {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    /// the available liquidity is the balance of `asset` in the vault
+    async fn get_flash_loans(&self, asset: &Asset, excluded: &Vec<Address>) -> Result<Vec<FlashLoan>, FlashLoanError> {
+        if excluded.contains(&self.vault.address()) {
+            return Ok(vec![]);
+        }
+
+        let (liquidity, fee) = futures::try_join!(
+            async {
+                let liquidity = multicall::call(self.context.multicall(), asset.contract.balance_of(self.vault.address())).await?;
+                return Ok::<U256, FlashLoanError>(liquidity);
+            },
+            self.get_fee()
+        )?;
+        if liquidity.is_zero() {
+            return Ok(vec![]);
+        }
+
+        return Ok(vec![FlashLoan {
+            kind: FlashLoanKind::Balancer,
+            lender: self.vault.address(),
+            fee,
+            liquidity,
+        }]);
+    }
+}
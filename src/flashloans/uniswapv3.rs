@@ -0,0 +1,80 @@
+use super::{FlashLoan, FlashLoanError, FlashLoanKind, FlashLoanProvider};
+use crate::assets::Asset;
+use crate::dexs::Dex;
+use async_trait::async_trait;
+use ethers::types::Address;
+use futures::future::join_all;
+use std::sync::Arc;
+
+/// uniswapV3 pools flash swaps, any pool holding the asset lends it for
+/// its swap fee, pools are looked up pairing the asset with `assets`
+pub struct UniswapV3Flash {
+    name: String,
+
+    /// uniswapV3 dex the pools are looked up in
+    dex: Arc<dyn Dex>,
+
+    /// assets the borrowed asset is paired with in the lending pools
+    assets: Vec<Asset>,
+}
+
+impl UniswapV3Flash {
+    pub fn new(name: String, dex: Arc<dyn Dex>, assets: Vec<Asset>) -> UniswapV3Flash {
+        UniswapV3Flash { name, dex, assets }
+    }
+}
+
+#[async_trait]
+impl FlashLoanProvider for UniswapV3Flash {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    /// every pool of `asset` with a balance of it is a lender, pairs are looked up
+    /// concurrently and the ones that fail are skipped
+    async fn get_flash_loans(&self, asset: &Asset, excluded: &Vec<Address>) -> Result<Vec<FlashLoan>, FlashLoanError> {
+        let pairs: Vec<&Asset> = self
+            .assets
+            .iter()
+            .filter(|pair_asset| pair_asset.address() != asset.address())
+            .collect();
+        let pairs_pools = join_all(pairs.iter().map(|pair_asset| self.dex.get_pools(asset, pair_asset))).await;
+
+        let mut pools = Vec::new();
+        for (pair_asset, pair_pools) in pairs.into_iter().zip(pairs_pools) {
+            let Ok(pair_pools) = pair_pools else {
+                continue
+            };
+            pools.extend(
+                pair_pools
+                    .into_iter()
+                    .filter(|pool| !excluded.contains(&pool.address))
+                    .map(|pool| (pool, pair_asset)),
+            );
+        }
+
+        let pools_liquidity = join_all(
+            pools
+                .iter()
+                .map(|(pool, pair_asset)| self.dex.get_pool_liquidity(pool, asset, pair_asset)),
+        )
+        .await;
+
+        let mut flash_loans = Vec::<FlashLoan>::with_capacity(pools.len());
+        for ((pool, _), pool_liquidity) in pools.iter().zip(pools_liquidity) {
+            let (Ok((liquidity, _)), Some(fee)) = (pool_liquidity, pool.fee_tier) else {
+                continue
+            };
+            if liquidity.is_zero() {
+                continue;
+            }
+            flash_loans.push(FlashLoan {
+                kind: FlashLoanKind::UniswapV3,
+                lender: pool.address,
+                fee,
+                liquidity,
+            });
+        }
+        return Ok(flash_loans);
+    }
+}
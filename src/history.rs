@@ -0,0 +1,218 @@
+use crate::db::{HistoryFilter, OpportunityRecord, Store, TradeRecord};
+use ethers::types::{Address, I256, U256};
+use tabled::{Table, Tabled};
+
+pub const USAGE: &str =
+    "usage: history <opportunities|trades> [--pair ADDRESS,ADDRESS] [--dex NAME] [--from UNIX_TIME] [--to UNIX_TIME]";
+
+/// records shown by the `history` subcommand
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HistoryKind {
+    Opportunities,
+    Trades,
+}
+
+#[derive(Tabled)]
+struct OpportunityRow {
+    timestamp: u64,
+    block: u64,
+
+    #[tabled(rename = "trade asset")]
+    asset_trade: String,
+
+    #[tabled(rename = "loan asset")]
+    asset_loan: String,
+
+    #[tabled(rename = "buy dex")]
+    buy_dex: String,
+
+    #[tabled(rename = "sell dex")]
+    sell_dex: String,
+
+    #[tabled(rename = "amount in")]
+    amount_in: String,
+
+    profit: String,
+
+    #[tabled(rename = "roi bps")]
+    roi_bps: String,
+
+    #[tabled(rename = "simulated profit")]
+    simulated_profit: String,
+
+    #[tabled(rename = "gas used")]
+    gas_used: String,
+
+    decision: String,
+    reason: String,
+}
+
+impl OpportunityRow {
+    fn new(opportunity: &OpportunityRecord) -> OpportunityRow {
+        let revert_reason = opportunity.revert_reason.as_ref().map(|reason| format!("reverted: {reason}"));
+        OpportunityRow {
+            timestamp: opportunity.timestamp,
+            block: opportunity.block,
+            asset_trade: format!("{:?}", opportunity.asset_trade),
+            asset_loan: format!("{:?}", opportunity.asset_loan),
+            buy_dex: opportunity.buy_dex.clone(),
+            sell_dex: opportunity.sell_dex.clone(),
+            amount_in: show_option(&opportunity.amount_in),
+            profit: show_option(&opportunity.profit),
+            roi_bps: show_option(&opportunity.roi_bps),
+            simulated_profit: show_option(&opportunity.simulated_profit),
+            gas_used: show_option(&opportunity.gas_used),
+            decision: format!("{:?}", opportunity.decision).to_lowercase(),
+            reason: show_option(&opportunity.reason.clone().or(revert_reason)),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct TradeRow {
+    timestamp: u64,
+    block: u64,
+
+    #[tabled(rename = "tx hash")]
+    tx_hash: String,
+
+    #[tabled(rename = "trade asset")]
+    asset_trade: String,
+
+    #[tabled(rename = "loan asset")]
+    asset_loan: String,
+
+    #[tabled(rename = "buy dex")]
+    buy_dex: String,
+
+    #[tabled(rename = "sell dex")]
+    sell_dex: String,
+
+    #[tabled(rename = "amount in")]
+    amount_in: U256,
+
+    #[tabled(rename = "gas used")]
+    gas_used: U256,
+
+    #[tabled(rename = "gas price")]
+    gas_price: U256,
+
+    profit: I256,
+}
+
+impl TradeRow {
+    fn new(trade: &TradeRecord) -> TradeRow {
+        TradeRow {
+            timestamp: trade.timestamp,
+            block: trade.block,
+            tx_hash: format!("{:?}", trade.tx_hash),
+            asset_trade: format!("{:?}", trade.asset_trade),
+            asset_loan: format!("{:?}", trade.asset_loan),
+            buy_dex: trade.buy_dex.clone(),
+            sell_dex: trade.sell_dex.clone(),
+            amount_in: trade.amount_in,
+            gas_used: trade.gas_used,
+            gas_price: trade.gas_price,
+            profit: trade.profit,
+        }
+    }
+}
+
+/// parse the arguments of the `history` subcommand, see `USAGE`
+pub fn parse_args(args: &[String]) -> Result<(HistoryKind, HistoryFilter), String> {
+    let kind = match args.first().map(String::as_str) {
+        Some("opportunities") => HistoryKind::Opportunities,
+        Some("trades") => HistoryKind::Trades,
+        _ => return Err(String::from(USAGE)),
+    };
+
+    let mut filter = HistoryFilter::default();
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let Some(value) = options.next() else {
+            return Err(format!("missing value of {option}"))
+        };
+        match option.as_str() {
+            "--pair" => {
+                let Some((asset0, asset1)) = value.split_once(',') else {
+                    return Err(format!("invalid pair {value}"))
+                };
+                let (Ok(asset0), Ok(asset1)) = (asset0.parse::<Address>(), asset1.parse::<Address>()) else {
+                    return Err(format!("invalid pair {value}"))
+                };
+                filter.pair = Some((asset0, asset1));
+            }
+            "--dex" => filter.dex = Some(value.clone()),
+            "--from" | "--to" => {
+                let Ok(timestamp) = value.parse::<u64>() else {
+                    return Err(format!("invalid time {value}"))
+                };
+                match option.as_str() {
+                    "--from" => filter.from = Some(timestamp),
+                    _ => filter.to = Some(timestamp),
+                }
+            }
+            _ => return Err(format!("unknown option {option}\n{USAGE}")),
+        }
+    }
+    return Ok((kind, filter));
+}
+
+/// print the records of `store` selected by the `history` subcommand `args`
+pub async fn show(store: &dyn Store, args: &[String]) -> Result<(), String> {
+    let (kind, filter) = parse_args(args)?;
+    let mut table = match kind {
+        HistoryKind::Opportunities => {
+            let opportunities = store.get_opportunities(&filter).await.map_err(|err| err.to_string())?;
+            Table::new(opportunities.iter().map(OpportunityRow::new))
+        }
+        HistoryKind::Trades => {
+            let trades = store.get_trades(&filter).await.map_err(|err| err.to_string())?;
+            Table::new(trades.iter().map(TradeRow::new))
+        }
+    };
+    println!("\n{}\n", table.with(tabled::Style::rounded()));
+    return Ok(());
+}
+
+#[inline]
+fn show_option<T: ToString>(value: &Option<T>) -> String {
+    return value.as_ref().map_or(String::from("-"), |value| value.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        return args.split_whitespace().map(String::from).collect();
+    }
+
+    #[test]
+    fn parse_history_args() {
+        let (asset0, asset1) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+        let (kind, filter) = parse_args(&args(&format!(
+            "trades --pair {:?},{:?} --dex uniswap --from 10 --to 20",
+            asset0, asset1
+        )))
+        .unwrap();
+        assert_eq!(kind, HistoryKind::Trades);
+        assert_eq!(filter.pair, Some((asset0, asset1)));
+        assert_eq!(filter.dex.as_deref(), Some("uniswap"));
+        assert_eq!((filter.from, filter.to), (Some(10), Some(20)));
+
+        let (kind, filter) = parse_args(&args("opportunities")).unwrap();
+        assert_eq!(kind, HistoryKind::Opportunities);
+        assert!(filter.pair.is_none() && filter.dex.is_none() && filter.from.is_none() && filter.to.is_none());
+    }
+
+    #[test]
+    fn reject_invalid_history_args() {
+        assert!(parse_args(&args("")).is_err());
+        assert!(parse_args(&args("pools")).is_err());
+        assert!(parse_args(&args("trades --dex")).is_err());
+        assert!(parse_args(&args("trades --pair 0x01")).is_err());
+        assert!(parse_args(&args("trades --from yesterday")).is_err());
+        assert!(parse_args(&args("trades --limit 10")).is_err());
+    }
+}
@@ -1,23 +1,52 @@
-// TODO: check different fees for uniswap v3
-// TODO: arbitrageur contract instance
-// TODO: check price on uniswapV2/quickswapV2(factory/pools or library)
 // TODO: swap on uniswapV2 and quickswapV2
 // TODO: redis db to save asset data
 // TODO: check about sqrt price https://ethereum.stackexchange.com/questions/98685/computing-the-uniswap-v3-pair-price-from-q64-96-number
 // TODO: diff between &(type1,type2) and (&type1,&type2)
 // TODO: search book "what things are called"
 mod addresses;
+mod amounts;
 mod assets;
+mod caller;
 mod configuration;
+mod context;
 mod contracts;
 mod db;
 mod dexs;
+mod events;
+mod flashloans;
+mod history;
+mod math;
+mod multicall;
+mod optimizer;
+mod pools;
+mod registry;
+mod routes;
+mod screening;
 mod watchers;
 
-use assets::Asset;
-use ethers::providers::{Http, Middleware, Provider};
-use redis::{Client, Connection};
-use std::{sync::Arc, thread, time};
+use assets::{Asset, AssetRepository};
+use caller::Caller;
+use context::Context;
+use dexs::{Dex, DexError, QuickswapV3, UniswapV2, UniswapV3};
+use flashloans::{AaveV3, Balancer, FlashLoanProvider, UniswapV3Flash};
+use multicall::Multicall;
+use pools::{PoolKind, PoolsState};
+use registry::{Factory, PoolRegistry};
+use routes::{Cycle, TokenGraph};
+use screening::{Screener, TokenRisk};
+use ethers::{
+    providers::{Http, Provider},
+    types::{Address, U256},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time,
+};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::JoinSet,
+};
 use watchers::Watcher;
 
 const POLLING_INTERVAL: u64 = 15_000;
@@ -28,9 +57,12 @@ async fn main() -> Result<(), ()> {
     // configuration
     ////////////////////////////////////////////////////////////////////////////
 
-    let Ok(conf) = configuration::new() else {
-        println!("error initializing configuration");
-        return Err(())
+    let conf = match configuration::new() {
+        Ok(conf) => conf,
+        Err(err) => {
+            println!("error initializing configuration: {err}");
+            return Err(());
+        }
     };
 
     let Ok(provider) = Provider::<Http>::try_from(conf.rpc_url) else {
@@ -38,25 +70,77 @@ async fn main() -> Result<(), ()> {
         return Err(())
     };
 
-    let Ok((trade_assets_addresses,loan_assets_addresses)) = configuration::get_assets(&conf.assets) else {
-        println!("error getting assets");
-        return Err(())
+    let (trade_assets_addresses, loan_assets_addresses) = match configuration::get_assets(&conf.assets) {
+        Ok(assets_addresses) => assets_addresses,
+        Err(err) => {
+            println!("error getting assets: {err}");
+            return Err(());
+        }
     };
 
-    let Ok(mut db_conn) = db::init(&conf.db_url).await else {
-        println!("error initializing db");
-        return Err(())
+    let store = match db::init(&conf.db_url).await {
+        Ok(store) => store,
+        Err(err) => {
+            println!("error initializing db: {err}");
+            return Err(());
+        }
     };
 
+    // `history` prints the stored opportunities or trades instead of watching
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("history") {
+        if let Err(err) = history::show(store.as_ref(), &args[1..]).await {
+            println!("{err}");
+            return Err(());
+        }
+        return Ok(());
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     // asset
     ////////////////////////////////////////////////////////////////////////////
 
     println!("init assets...");
-    let trade_assets: Vec<Asset> =
-        assets::vec_from_addresses(trade_assets_addresses, &provider, &mut db_conn).await;
-    let loan_assets: Vec<Asset> =
-        assets::vec_from_addresses(loan_assets_addresses, &provider, &mut db_conn).await;
+    let asset_repository = AssetRepository::new(store.clone(), provider.clone());
+    let trade_assets: Vec<Asset> = assets::vec_from_addresses(trade_assets_addresses, &asset_repository).await;
+    let loan_assets: Vec<Asset> = assets::vec_from_addresses(loan_assets_addresses, &asset_repository).await;
+
+    let Some(native_asset_address) = addresses::get_native_asset_address() else {
+        return Err(())
+    };
+    let native_asset = match asset_repository.get(&native_asset_address).await {
+        Ok(native_asset) => native_asset,
+        Err(err) => {
+            println!("error getting native asset: {err}");
+            return Err(());
+        }
+    };
+
+    // assets are only watched once screened and if all their risks are accepted
+    println!("screen assets...");
+    let Some(multicall_address) = addresses::get_multicall_address() else {
+        return Err(())
+    };
+    // assets are screened on a local fork of the chain when one is configured
+    let screener_provider = match &conf.screening_fork_url {
+        Some(fork_url) => {
+            let Ok(fork_provider) = Provider::<Http>::try_from(fork_url.as_str()) else {
+                println!("error initializing screening fork provider");
+                return Err(())
+            };
+            fork_provider
+        }
+        None => provider.clone(),
+    };
+    let screener = Screener::new(Arc::new(screener_provider), multicall_address);
+    let trade_assets = get_safe_assets(
+        asset_repository.screen(trade_assets, &screener).await,
+        &conf.accepted_token_risks,
+    );
+    let loan_assets = get_safe_assets(
+        asset_repository.screen(loan_assets, &screener).await,
+        &conf.accepted_token_risks,
+    );
 
     println!("loan assets:\n");
     loan_assets
@@ -67,8 +151,26 @@ async fn main() -> Result<(), ()> {
         .iter()
         .for_each(|trade_asset| println!("{}", trade_asset.symbol));
 
-    let assets_pairs: Vec<(Asset, Asset)> =
-        assets::pairs_from_addresses(&trade_assets, &loan_assets);
+    let assets_pairs: Vec<(Asset, Asset)> = assets::pairs_from_addresses(&trade_assets, &loan_assets);
+
+    // configured amounts are parsed once to the smallest unit of their asset
+    let min_net_profit = match dexs::from_decimal(&conf.min_net_profit, native_asset.decimals()) {
+        Ok(min_net_profit) => min_net_profit,
+        Err(_) => {
+            println!("error parsing min net profit {}", conf.min_net_profit);
+            return Err(());
+        }
+    };
+    let mut min_pool_liquidity = Vec::<(Asset, U256)>::with_capacity(loan_assets.len());
+    for loan_asset in loan_assets.iter() {
+        match dexs::from_decimal(&conf.min_pool_liquidity, loan_asset.decimals()) {
+            Ok(min_balance) => min_pool_liquidity.push((loan_asset.clone(), min_balance)),
+            Err(_) => {
+                println!("error parsing min pool liquidity {}", conf.min_pool_liquidity);
+                return Err(());
+            }
+        }
+    }
 
     ////////////////////////////////////////////////////////////////////////////
     // dexs
@@ -86,46 +188,335 @@ async fn main() -> Result<(), ()> {
         return Err(())
     };
 
-    let (uni, quick) = (
-        dexs::Dex::UniswapV3 {
-            name: String::from("uniswap"),
-            factory: uniswapv3_factory_address,
-            quoter: uniswapv3_quoter_address,
-            provider: Arc::new(provider.clone()),
-        },
-        dexs::Dex::QuickswapV3 {
-            name: String::from("quickswap"),
-            factory: quickswapv3_factory_address,
-            quoter: quickswapv3_quoter_address,
-            provider: Arc::new(provider.clone()),
-        },
+    let multicall = Arc::new(Multicall::new(multicall_address, Arc::new(provider.clone())));
+    let pools_state = Arc::new(PoolsState::new(Arc::new(provider.clone())));
+
+    // dexs are named after their factories, the registry serves the pools of a
+    // dex by the address of its factory
+    let uniswapv3_factory = Factory::new("uniswap", uniswapv3_factory_address, PoolKind::UniswapV3);
+    let quickswapv3_factory = Factory::new("quickswap", quickswapv3_factory_address, PoolKind::Algebra);
+    let uniswapv2_factory = Factory::new("uniswapv2", uniswapv2_factory_address, PoolKind::UniswapV2);
+    let quickswapv2_factory = Factory::new("quickswapv2", quickswapv2_factory_address, PoolKind::UniswapV2);
+
+    // pools are discovered from the factories events when a start block is set,
+    // pools discovered later are sent through `registry_updates` to be watched
+    let (registry_updates_sender, mut registry_updates) = mpsc::unbounded_channel::<Vec<Address>>();
+    let registry: Option<Arc<PoolRegistry>> = match conf.pools_start_block {
+        Some(start_block) => {
+            println!("init pools registry...");
+            let registry = PoolRegistry::new(
+                Arc::new(provider.clone()),
+                store.clone(),
+                vec![
+                    uniswapv3_factory.clone(),
+                    quickswapv3_factory.clone(),
+                    uniswapv2_factory.clone(),
+                    quickswapv2_factory.clone(),
+                ],
+                [trade_assets.clone(), loan_assets.clone(), vec![native_asset.clone()]].concat(),
+            )
+            .with_min_liquidity(min_pool_liquidity)
+            .with_multicall(multicall.clone());
+            if let Err(err) = registry.load(start_block).await {
+                println!("error loading pools registry: {err}");
+                return Err(());
+            }
+
+            let registry = Arc::new(registry);
+            tokio::spawn(registry.clone().follow(POLLING_INTERVAL, registry_updates_sender));
+            Some(registry)
+        }
+        None => None,
+    };
+
+    let context = Arc::new(
+        Context::new(Arc::new(provider.clone()))
+            .with_multicall(multicall.clone())
+            .with_pools_state(pools_state.clone())
+            .with_registry(registry.clone()),
     );
 
+    let dexs_list: Vec<Arc<dyn Dex>> = vec![
+        Arc::new(
+            UniswapV3::new(
+                uniswapv3_factory.dex.clone(),
+                uniswapv3_factory.address,
+                uniswapv3_quoter_address,
+                context.clone(),
+            )
+            .with_cross_check(conf.cross_check_quotes),
+        ),
+        Arc::new(QuickswapV3::new(
+            quickswapv3_factory.dex.clone(),
+            quickswapv3_factory.address,
+            quickswapv3_quoter_address,
+            context.clone(),
+        )),
+        Arc::new(UniswapV2::new(
+            uniswapv2_factory.dex.clone(),
+            uniswapv2_factory.address,
+            context.clone(),
+        )),
+        Arc::new(UniswapV2::new(
+            quickswapv2_factory.dex.clone(),
+            quickswapv2_factory.address,
+            context.clone(),
+        )),
+    ];
+
+    ////////////////////////////////////////////////////////////////////////////
+    // flash loans
+    ////////////////////////////////////////////////////////////////////////////
+
+    println!("init flash loan providers...");
+    let Some((aavev3_pool_address, balancer_vault_address)) = addresses::get_flash_loan_addresses() else {
+        return Err(())
+    };
+
+    let mut flash_loan_providers: Vec<Arc<dyn FlashLoanProvider>> = vec![
+        Arc::new(AaveV3::new(String::from("aaveV3"), aavev3_pool_address, context.clone())),
+        Arc::new(Balancer::new(String::from("balancer"), balancer_vault_address, context.clone())),
+    ];
+    // uniswap dex, pools are paired with every asset
+    match dexs_list.iter().find(|dex| dex.pool_kind() == PoolKind::UniswapV3) {
+        Some(uniswapv3_dex) => flash_loan_providers.push(Arc::new(UniswapV3Flash::new(
+            uniswapv3_dex.name(),
+            uniswapv3_dex.clone(),
+            [trade_assets.clone(), loan_assets.clone(), vec![native_asset.clone()]].concat(),
+        ))),
+        None => println!("error initializing uniswapV3 flash loans: no uniswapV3 dex"),
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // caller
+    ////////////////////////////////////////////////////////////////////////////
+
+    println!("init caller...");
+    let Ok(arbitrageur_address) = conf.arbitrageur.parse::<Address>() else {
+        println!("error parsing arbitrageur address");
+        return Err(())
+    };
+
+    let caller = match Caller::new(provider.clone(), &conf.private_key, arbitrageur_address).await {
+        Ok(caller) => caller,
+        Err(err) => {
+            println!("error initializing caller: {err}");
+            return Err(());
+        }
+    };
+    let caller = caller.with_trace_simulations(conf.trace_simulations);
+    let caller = match &conf.state_overrides {
+        Some(state_overrides_path) => match configuration::get_state_overrides(state_overrides_path) {
+            Ok(state_overrides) => caller.with_state_overrides(state_overrides),
+            Err(err) => {
+                println!("error getting state overrides: {err}");
+                return Err(());
+            }
+        },
+        None => caller,
+    };
+    let caller = Arc::new(caller);
+
     ////////////////////////////////////////////////////////////////////////////
     // watchers
     ////////////////////////////////////////////////////////////////////////////
 
-    println!("init watchers...");
-    let mut watchers_list: Vec<Watcher> = assets_pairs
-        .iter()
-        .map(|pair: &(Asset, Asset)| {
-            return Watcher::from_pairs(
+    let new_watcher = |pair: &(Asset, Asset)| {
+        return Arc::new(
+            Watcher::from_pairs(
                 pair.clone(),
-                (uni.clone(), quick.clone()),
+                native_asset.clone(),
+                min_net_profit,
+                dexs_list.clone(),
+                flash_loan_providers.clone(),
+                caller.clone(),
                 Arc::new(provider.clone()),
-            );
-        })
-        .collect();
+            )
+            .with_max_pool_share_bps(conf.max_pool_share_bps)
+            .with_slippage_tolerance_bps(conf.slippage_tolerance_bps)
+            .with_store(store.clone()),
+        );
+    };
+
+    // watchers, cycles and the pools they depend on are built on the first
+    // iteration and rebuilt whenever the registry discovers new pools
+    let mut watchers_list: Vec<Arc<Watcher>> = vec![];
+    let mut watched_pairs = HashSet::<(Address, Address)>::new();
+    let mut cycles_list: Vec<Arc<Cycle>> = vec![];
+    let mut pools_list: Vec<(Address, PoolKind)> = vec![];
+    let mut watchers_by_pool = HashMap::<Address, Vec<usize>>::new();
+    let mut cycles_by_pool = HashMap::<Address, Vec<usize>>::new();
+    let mut pools_discovered = true;
 
     println!("init watch loop");
+    let semaphore = Arc::new(Semaphore::new(conf.max_concurrent_watchers));
     loop {
-        for watcher in watchers_list.iter_mut() {
-            if let Err(err) = watcher.watch().await {
-                println!("error ocurred: {:?}", err);
-                continue;
+        if pools_discovered {
+            // with the registry, pairs with less than two pools can't be arbitraged
+            println!("init watchers...");
+            for pair in assets_pairs.iter() {
+                let pair_addresses = (pair.0.address(), pair.1.address());
+                if watched_pairs.contains(&pair_addresses) {
+                    continue;
+                }
+                if let Some(registry) = &registry {
+                    if registry.count_pools(pair_addresses.0, pair_addresses.1) < 2 {
+                        continue;
+                    }
+                }
+                watchers_list.push(new_watcher(pair));
+                watched_pairs.insert(pair_addresses);
             }
+            println!("{} pairs watched", watchers_list.len());
+
+            println!("init cycles...");
+            let graph = TokenGraph::new([trade_assets.clone(), loan_assets.clone()].concat(), &dexs_list).await;
+            cycles_list = loan_assets
+                .iter()
+                .flat_map(|loan_asset| graph.find_cycles(loan_asset))
+                .map(Arc::new)
+                .collect();
+            println!("{} cycles found", cycles_list.len());
+
+            println!("init events...");
+            let mut pools_kinds = HashMap::<Address, PoolKind>::new();
+            watchers_by_pool.clear();
+            for (idx, watcher) in watchers_list.iter().enumerate() {
+                for (dex, pool) in watcher.get_pools().await {
+                    pools_kinds.insert(pool.address, dex.pool_kind());
+                    watchers_by_pool.entry(pool.address).or_default().push(idx);
+                }
+            }
+            cycles_by_pool.clear();
+            for (idx, cycle) in cycles_list.iter().enumerate() {
+                for (dex, pool) in cycle.get_pools() {
+                    pools_kinds.insert(pool.address, dex.pool_kind());
+                    cycles_by_pool.entry(pool.address).or_default().push(idx);
+                }
+            }
+            pools_list = pools_kinds.into_iter().collect();
+            pools_discovered = false;
         }
+
+        // re-evaluate only the watchers and cycles of updated pools while
+        // subscribed to pools events, poll all of them if the socket drops,
+        // the subscription is renewed when the registry discovers new pools
+        if let Some(ws_url) = &conf.ws_url {
+            let pools_addresses: Vec<Address> = pools_list.iter().map(|(address, _)| *address).collect();
+            if let Ok(mut receiver) = events::listen(ws_url.clone(), pools_addresses).await {
+                println!("subscribed to pools events");
+
+                // pools states are loaded once subscribed so no event is missed,
+                // events already included in them are skipped
+                match pools_state.load(&pools_list).await {
+                    Ok(()) => pools_state.set_synced(true),
+                    Err(err) => println!("error loading pools states: {err}"),
+                }
+                loop {
+                    tokio::select! {
+                        logs = receiver.recv() => {
+                            let Some(logs) = logs else {
+                                break
+                            };
+                            pools_state.apply_logs(&logs);
+                            let updated_pools: HashSet<Address> = logs.iter().map(|log| log.address).collect();
+                            let (watchers_idxs, cycles_idxs) = (
+                                get_affected(&updated_pools, &watchers_by_pool),
+                                get_affected(&updated_pools, &cycles_by_pool),
+                            );
+                            watch_concurrently(
+                                watchers_idxs.into_iter().map(|idx| watchers_list[idx].clone()).collect(),
+                                cycles_idxs.into_iter().map(|idx| cycles_list[idx].clone()).collect(),
+                                &semaphore,
+                            )
+                            .await;
+                        }
+                        Some(new_pools) = registry_updates.recv() => {
+                            println!("{} pools discovered, resubscribing...", new_pools.len());
+                            pools_discovered = true;
+                            break;
+                        }
+                    }
+                }
+                pools_state.set_synced(false);
+                if pools_discovered {
+                    continue;
+                }
+                println!("events subscription dropped, polling...");
+            }
+        }
+
+        while let Ok(new_pools) = registry_updates.try_recv() {
+            println!("{} pools discovered", new_pools.len());
+            pools_discovered = true;
+        }
+        watch_concurrently(watchers_list.clone(), cycles_list.clone(), &semaphore).await;
         println!("iteration completed...");
-        thread::sleep(time::Duration::from_millis(POLLING_INTERVAL));
+        tokio::time::sleep(time::Duration::from_millis(POLLING_INTERVAL)).await;
+    }
+}
+
+/// evaluate `watchers` and `cycles` in tasks of the runtime, as many at
+/// the same time as `semaphore` permits, and wait for all of them
+async fn watch_concurrently(watchers: Vec<Arc<Watcher>>, cycles: Vec<Arc<Cycle>>, semaphore: &Arc<Semaphore>) {
+    let mut tasks = JoinSet::<Result<(), DexError>>::new();
+    for watcher in watchers {
+        let Ok(permit) = semaphore.clone().acquire_owned().await else {
+            return
+        };
+        tasks.spawn(async move {
+            let result = watcher.watch().await;
+            drop(permit);
+            return result;
+        });
+    }
+    for cycle in cycles {
+        let Ok(permit) = semaphore.clone().acquire_owned().await else {
+            return
+        };
+        tasks.spawn(async move {
+            let result = cycle.watch().await;
+            drop(permit);
+            return result;
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        if let Ok(Err(err)) = result {
+            println!("error ocurred: {:?}", err);
+        }
     }
 }
+
+/// get the `assets` that are safe to watch with `accepted_risks`
+fn get_safe_assets(assets: Vec<Asset>, accepted_risks: &Vec<TokenRisk>) -> Vec<Asset> {
+    return assets
+        .into_iter()
+        .filter(|asset| {
+            if asset.is_safe(accepted_risks) {
+                return true;
+            }
+            // assets that failed to be screened are not risky, their risks are unknown
+            match (&asset.risks, &asset.screening_error) {
+                (Some(risks), _) => println!(
+                    "skipping unsafe asset {}: {}",
+                    asset.symbol,
+                    risks.iter().map(|risk| risk.to_string()).collect::<Vec<String>>().join(", ")
+                ),
+                (None, Some(err)) => println!("skipping asset {} that failed screening: {err}", asset.symbol),
+                (None, None) => println!("skipping unscreened asset {}", asset.symbol),
+            }
+            return false;
+        })
+        .collect();
+}
+
+/// get indexes of the items that depend on `updated_pools`
+fn get_affected(updated_pools: &HashSet<Address>, items_by_pool: &HashMap<Address, Vec<usize>>) -> HashSet<usize> {
+    return updated_pools
+        .iter()
+        .filter_map(|pool_address| items_by_pool.get(pool_address))
+        .flatten()
+        .cloned()
+        .collect();
+}
@@ -0,0 +1,545 @@
+// port of the uniswapV3 core libraries needed to compute swaps off-chain,
+// TickMath, SqrtPriceMath, SwapMath, FullMath and TickBitmap
+use ethers::types::{I256, U256, U512};
+use std::collections::HashMap;
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
+const MIN_SQRT_RATIO: u64 = 4295128739;
+const MAX_SQRT_RATIO: &str = "fffd8963efd1fc6a506488495d951d5263988d26";
+const FEE_DENOMINATOR: u32 = 1_000_000;
+const RESOLUTION: usize = 96;
+
+// TickMath.getSqrtRatioAtTick factors, 1/sqrt(1.0001)^(2^i) as Q128.128
+const SQRT_RATIO_FACTORS: [&str; 19] = [
+    "fff97272373d413259a46990580e213a",
+    "fff2e50f5f656932ef12357cf3c7fdcc",
+    "ffe5caca7e10e4e61c3624eaa0941cd0",
+    "ffcb9843d60f6159c9db58835c926644",
+    "ff973b41fa98c081472e6896dfb254c0",
+    "ff2ea16466c96a3843ec78b326b52861",
+    "fe5dee046a99a2a811c461f1969c3053",
+    "fcbe86c7900a88aedcffc83b479aa3a4",
+    "f987a7253ac413176f2b074cf7815e54",
+    "f3392b0822b70005940c7a398e4b70f3",
+    "e7159475a2c29b7443b29c7fa6e889d9",
+    "d097f3bdfd2022b8845ad8f792aa5825",
+    "a9f746462d870fdf8a65dc1f90e061e5",
+    "70d869a156d2a1b890bb3df62baf32f7",
+    "31be135f97d08fd981231505542fcfa6",
+    "9aa508b5b7a84e1c677de54f3e99bc9",
+    "5d6af8dedb81196699c329225ee604",
+    "2216e584f5fa1ea926041bedfe98",
+    "48a170391f7dc42444e8fa2",
+];
+const SQRT_RATIO_FIRST_FACTOR: &str = "fffcb933bd6fad37aa2d162d1a594001";
+
+/// state of a concentrated liquidity pool needed to simulate swaps
+#[derive(Clone, Debug, Default)]
+pub struct ConcentratedLiquidity {
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    pub tick_spacing: i32,
+
+    /// fee in hundredths of a bip
+    pub fee: u32,
+
+    /// tick bitmap words loaded, keyed by word position
+    pub tick_bitmap: HashMap<i16, U256>,
+
+    /// (liquidity_gross,liquidity_net) of initialized ticks
+    pub ticks: HashMap<i32, (u128, i128)>,
+}
+
+impl ConcentratedLiquidity {
+    /// compute the output amount of swapping `amount_in`, token0 for token1 if
+    /// `zero_for_one`, `None` if the swap crosses a tick bitmap word not loaded
+    pub fn quote_exact_input(&self, amount_in: U256, zero_for_one: bool) -> Option<U256> {
+        let sqrt_price_limit = if zero_for_one {
+            U256::from(MIN_SQRT_RATIO) + 1
+        } else {
+            U256::from_str_radix(MAX_SQRT_RATIO, 16).ok()? - 1
+        };
+
+        let (mut amount_remaining, mut amount_out) = (amount_in, U256::zero());
+        let (mut sqrt_price, mut tick, mut liquidity) = (self.sqrt_price_x96, self.tick, self.liquidity);
+
+        while !amount_remaining.is_zero() && sqrt_price != sqrt_price_limit {
+            let (tick_next, initialized) = self.next_initialized_tick_within_one_word(tick, zero_for_one)?;
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next = get_sqrt_ratio_at_tick(tick_next)?;
+
+            let sqrt_price_target = if zero_for_one {
+                sqrt_price_next.max(sqrt_price_limit)
+            } else {
+                sqrt_price_next.min(sqrt_price_limit)
+            };
+            let sqrt_price_start = sqrt_price;
+            let (sqrt_price_after, step_in, step_out, step_fee) = compute_swap_step(
+                sqrt_price,
+                sqrt_price_target,
+                liquidity,
+                I256::try_from(amount_remaining).ok()?,
+                self.fee,
+            )?;
+
+            sqrt_price = sqrt_price_after;
+            amount_remaining = amount_remaining.checked_sub(step_in + step_fee)?;
+            amount_out += step_out;
+
+            if sqrt_price == sqrt_price_next {
+                if initialized {
+                    let (_, liquidity_net) = self.ticks.get(&tick_next).cloned().unwrap_or_default();
+                    let liquidity_net = if zero_for_one { -liquidity_net } else { liquidity_net };
+                    liquidity = add_delta(liquidity, liquidity_net)?;
+                }
+                tick = if zero_for_one { tick_next - 1 } else { tick_next };
+            } else if sqrt_price != sqrt_price_start {
+                tick = get_tick_at_sqrt_ratio(sqrt_price)?;
+            }
+        }
+
+        return Some(amount_out);
+    }
+
+    /// TickBitmap.nextInitializedTickWithinOneWord over the loaded words
+    fn next_initialized_tick_within_one_word(&self, tick: i32, lte: bool) -> Option<(i32, bool)> {
+        let mut compressed = tick / self.tick_spacing;
+        if tick < 0 && tick % self.tick_spacing != 0 {
+            compressed -= 1;
+        }
+
+        if lte {
+            let (word_pos, bit_pos) = tick_position(compressed);
+            let word = self.tick_bitmap.get(&word_pos)?;
+            let mask = (U256::one() << bit_pos) - 1 + (U256::one() << bit_pos);
+            let masked = *word & mask;
+            let initialized = !masked.is_zero();
+            let next = if initialized {
+                let most_significant_bit = (masked.bits() - 1) as i32;
+                (compressed - (bit_pos as i32 - most_significant_bit)) * self.tick_spacing
+            } else {
+                (compressed - bit_pos as i32) * self.tick_spacing
+            };
+            return Some((next, initialized));
+        } else {
+            let (word_pos, bit_pos) = tick_position(compressed + 1);
+            let word = self.tick_bitmap.get(&word_pos)?;
+            let mask = !((U256::one() << bit_pos) - 1);
+            let masked = *word & mask;
+            let initialized = !masked.is_zero();
+            let next = if initialized {
+                let least_significant_bit = masked.trailing_zeros() as i32;
+                (compressed + 1 + (least_significant_bit - bit_pos as i32)) * self.tick_spacing
+            } else {
+                (compressed + 1 + (255 - bit_pos as i32)) * self.tick_spacing
+            };
+            return Some((next, initialized));
+        }
+    }
+
+    /// update the pool with a position liquidity change of `liquidity_delta` between
+    /// `tick_lower` and `tick_upper`, mirrors Pool._modifyPosition
+    pub fn modify_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) {
+        self.update_tick(tick_lower, liquidity_delta, false);
+        self.update_tick(tick_upper, liquidity_delta, true);
+        if tick_lower <= self.tick && self.tick < tick_upper {
+            self.liquidity = add_delta(self.liquidity, liquidity_delta).unwrap_or_default();
+        }
+    }
+
+    fn update_tick(&mut self, tick: i32, liquidity_delta: i128, upper: bool) {
+        let (liquidity_gross, liquidity_net) = self.ticks.get(&tick).cloned().unwrap_or_default();
+        let liquidity_gross_after = add_delta(liquidity_gross, liquidity_delta).unwrap_or_default();
+        let liquidity_net_after = if upper {
+            liquidity_net - liquidity_delta
+        } else {
+            liquidity_net + liquidity_delta
+        };
+
+        // tick flipped between initialized and uninitialized
+        if (liquidity_gross == 0) != (liquidity_gross_after == 0) {
+            let (word_pos, bit_pos) = tick_position(tick / self.tick_spacing);
+            if let Some(word) = self.tick_bitmap.get_mut(&word_pos) {
+                *word ^= U256::one() << bit_pos;
+            }
+        }
+
+        if liquidity_gross_after == 0 {
+            self.ticks.remove(&tick);
+        } else {
+            self.ticks.insert(tick, (liquidity_gross_after, liquidity_net_after));
+        }
+    }
+}
+
+/// word position and bit position of a compressed tick in the tick bitmap
+#[inline]
+pub fn tick_position(compressed: i32) -> (i16, u8) {
+    return ((compressed >> 8) as i16, (compressed & 0xff) as u8);
+}
+
+/// TickMath.getSqrtRatioAtTick, sqrt(1.0001^tick) as Q64.96
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Option<U256> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = tick.unsigned_abs();
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from_str_radix(SQRT_RATIO_FIRST_FACTOR, 16).ok()?
+    } else {
+        U256::one() << 128
+    };
+    for (idx, factor) in SQRT_RATIO_FACTORS.iter().enumerate() {
+        if abs_tick & (0x2 << idx) != 0 {
+            ratio = (ratio * U256::from_str_radix(factor, 16).ok()?) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // round up to go from Q128.128 to Q64.96
+    let rounding = if (ratio % (U256::one() << 32)).is_zero() { 0 } else { 1 };
+    return Some((ratio >> 32) + rounding);
+}
+
+/// TickMath.getTickAtSqrtRatio, the greatest tick whose sqrt ratio is not above
+/// `sqrt_price_x96`, searched by bisection over `get_sqrt_ratio_at_tick`
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Option<i32> {
+    if sqrt_price_x96 < U256::from(MIN_SQRT_RATIO) || sqrt_price_x96 >= U256::from_str_radix(MAX_SQRT_RATIO, 16).ok()? {
+        return None;
+    }
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let tick = low + (high - low + 1) / 2;
+        if get_sqrt_ratio_at_tick(tick)? <= sqrt_price_x96 {
+            low = tick;
+        } else {
+            high = tick - 1;
+        }
+    }
+    return Some(low);
+}
+
+/// SwapMath.computeSwapStep, `amount_remaining` is the input left to swap if positive
+/// and the output left to receive if negative, returns
+/// (sqrt_price_next,amount_in,amount_out,fee_amount)
+pub fn compute_swap_step(
+    sqrt_price_current: U256,
+    sqrt_price_target: U256,
+    liquidity: u128,
+    amount_remaining: I256,
+    fee: u32,
+) -> Option<(U256, U256, U256, U256)> {
+    let zero_for_one = sqrt_price_current >= sqrt_price_target;
+    let exact_in = !amount_remaining.is_negative();
+    let amount_remaining_abs = amount_remaining.unsigned_abs();
+    let liquidity = U256::from(liquidity);
+
+    let (max_amount_in, max_amount_out, sqrt_price_next) = if exact_in {
+        let amount_remaining_less_fee = mul_div(
+            amount_remaining_abs,
+            U256::from(FEE_DENOMINATOR - fee),
+            U256::from(FEE_DENOMINATOR),
+        )?;
+        let max_amount_in = if zero_for_one {
+            get_amount0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+        } else {
+            get_amount1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)?
+        };
+        let sqrt_price_next = if amount_remaining_less_fee >= max_amount_in {
+            sqrt_price_target
+        } else {
+            get_next_sqrt_price_from_input(sqrt_price_current, liquidity, amount_remaining_less_fee, zero_for_one)?
+        };
+        (Some(max_amount_in), None, sqrt_price_next)
+    } else {
+        let max_amount_out = if zero_for_one {
+            get_amount1_delta(sqrt_price_target, sqrt_price_current, liquidity, false)?
+        } else {
+            get_amount0_delta(sqrt_price_current, sqrt_price_target, liquidity, false)?
+        };
+        let sqrt_price_next = if amount_remaining_abs >= max_amount_out {
+            sqrt_price_target
+        } else {
+            get_next_sqrt_price_from_output(sqrt_price_current, liquidity, amount_remaining_abs, zero_for_one)?
+        };
+        (None, Some(max_amount_out), sqrt_price_next)
+    };
+    let max = sqrt_price_next == sqrt_price_target;
+
+    let (amount_in, amount_out) = if zero_for_one {
+        (
+            match max_amount_in {
+                Some(max_amount_in) if max => max_amount_in,
+                _ => get_amount0_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?,
+            },
+            match max_amount_out {
+                Some(max_amount_out) if max => max_amount_out,
+                _ => get_amount1_delta(sqrt_price_next, sqrt_price_current, liquidity, false)?,
+            },
+        )
+    } else {
+        (
+            match max_amount_in {
+                Some(max_amount_in) if max => max_amount_in,
+                _ => get_amount1_delta(sqrt_price_current, sqrt_price_next, liquidity, true)?,
+            },
+            match max_amount_out {
+                Some(max_amount_out) if max => max_amount_out,
+                _ => get_amount0_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?,
+            },
+        )
+    };
+    // the output is capped at the remaining one
+    let amount_out = if exact_in { amount_out } else { amount_out.min(amount_remaining_abs) };
+
+    let fee_amount = if exact_in && !max {
+        amount_remaining_abs.checked_sub(amount_in)?
+    } else {
+        mul_div_rounding_up(amount_in, U256::from(fee), U256::from(FEE_DENOMINATOR - fee))?
+    };
+
+    return Some((sqrt_price_next, amount_in, amount_out, fee_amount));
+}
+
+/// SqrtPriceMath.getNextSqrtPriceFromInput
+fn get_next_sqrt_price_from_input(
+    sqrt_price: U256,
+    liquidity: U256,
+    amount_in: U256,
+    zero_for_one: bool,
+) -> Option<U256> {
+    if sqrt_price.is_zero() || liquidity.is_zero() {
+        return None;
+    }
+
+    if zero_for_one {
+        return get_next_sqrt_price_from_amount0_rounding_up(sqrt_price, liquidity, amount_in, true);
+    } else {
+        return get_next_sqrt_price_from_amount1_rounding_down(sqrt_price, liquidity, amount_in, true);
+    }
+}
+
+/// SqrtPriceMath.getNextSqrtPriceFromOutput
+fn get_next_sqrt_price_from_output(
+    sqrt_price: U256,
+    liquidity: U256,
+    amount_out: U256,
+    zero_for_one: bool,
+) -> Option<U256> {
+    if sqrt_price.is_zero() || liquidity.is_zero() {
+        return None;
+    }
+
+    if zero_for_one {
+        return get_next_sqrt_price_from_amount1_rounding_down(sqrt_price, liquidity, amount_out, false);
+    } else {
+        return get_next_sqrt_price_from_amount0_rounding_up(sqrt_price, liquidity, amount_out, false);
+    }
+}
+
+/// SqrtPriceMath.getNextSqrtPriceFromAmount0RoundingUp
+fn get_next_sqrt_price_from_amount0_rounding_up(
+    sqrt_price: U256,
+    liquidity: U256,
+    amount: U256,
+    add: bool,
+) -> Option<U256> {
+    if amount.is_zero() {
+        return Some(sqrt_price);
+    }
+
+    let numerator1 = liquidity << RESOLUTION;
+    let product = amount.checked_mul(sqrt_price);
+    if add {
+        if let Some(denominator) = product.and_then(|product| numerator1.checked_add(product)) {
+            return mul_div_rounding_up(numerator1, sqrt_price, denominator);
+        }
+        return Some(div_rounding_up(numerator1, numerator1 / sqrt_price + amount));
+    } else {
+        let denominator = numerator1.checked_sub(product?).filter(|denominator| !denominator.is_zero())?;
+        return mul_div_rounding_up(numerator1, sqrt_price, denominator);
+    }
+}
+
+/// SqrtPriceMath.getNextSqrtPriceFromAmount1RoundingDown
+fn get_next_sqrt_price_from_amount1_rounding_down(
+    sqrt_price: U256,
+    liquidity: U256,
+    amount: U256,
+    add: bool,
+) -> Option<U256> {
+    if add {
+        let quotient = mul_div(amount, U256::one() << RESOLUTION, liquidity)?;
+        return sqrt_price.checked_add(quotient);
+    } else {
+        let quotient = mul_div_rounding_up(amount, U256::one() << RESOLUTION, liquidity)?;
+        return sqrt_price.checked_sub(quotient).filter(|sqrt_price_next| !sqrt_price_next.is_zero());
+    }
+}
+
+/// SqrtPriceMath.getAmount0Delta
+fn get_amount0_delta(sqrt_ratio_a: U256, sqrt_ratio_b: U256, liquidity: U256, round_up: bool) -> Option<U256> {
+    let (sqrt_ratio_a, sqrt_ratio_b) = if sqrt_ratio_a > sqrt_ratio_b {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    } else {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    };
+    if sqrt_ratio_a.is_zero() {
+        return None;
+    }
+
+    let numerator1 = liquidity << RESOLUTION;
+    let numerator2 = sqrt_ratio_b - sqrt_ratio_a;
+    if round_up {
+        let amount = mul_div_rounding_up(numerator1, numerator2, sqrt_ratio_b)?;
+        return Some(div_rounding_up(amount, sqrt_ratio_a));
+    } else {
+        return Some(mul_div(numerator1, numerator2, sqrt_ratio_b)? / sqrt_ratio_a);
+    }
+}
+
+/// SqrtPriceMath.getAmount1Delta
+fn get_amount1_delta(sqrt_ratio_a: U256, sqrt_ratio_b: U256, liquidity: U256, round_up: bool) -> Option<U256> {
+    let (sqrt_ratio_a, sqrt_ratio_b) = if sqrt_ratio_a > sqrt_ratio_b {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    } else {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    };
+
+    let q96 = U256::one() << RESOLUTION;
+    if round_up {
+        return mul_div_rounding_up(liquidity, sqrt_ratio_b - sqrt_ratio_a, q96);
+    } else {
+        return mul_div(liquidity, sqrt_ratio_b - sqrt_ratio_a, q96);
+    }
+}
+
+/// FullMath.mulDiv, `a * b / denominator` with a 512 bits intermediate product
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let result = a.full_mul(b) / U512::from(denominator);
+    return U256::try_from(result).ok();
+}
+
+/// FullMath.mulDivRoundingUp
+pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    let result = mul_div(a, b, denominator)?;
+    if (a.full_mul(b) % U512::from(denominator)).is_zero() {
+        return Some(result);
+    }
+    return result.checked_add(U256::one());
+}
+
+#[inline]
+fn div_rounding_up(a: U256, b: U256) -> U256 {
+    let rounding = if (a % b).is_zero() { 0 } else { 1 };
+    return a / b + rounding;
+}
+
+/// LiquidityMath.addDelta
+#[inline]
+pub fn add_delta(liquidity: u128, delta: i128) -> Option<u128> {
+    if delta < 0 {
+        return liquidity.checked_sub(delta.unsigned_abs());
+    }
+    return liquidity.checked_add(delta as u128);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // vectors of the uniswapV3 core TickMath and SwapMath tests
+    const SQRT_PRICE_1_1: &str = "79228162514264337593543950336";
+    const SQRT_PRICE_101_100: &str = "79623317895830914510639640423";
+    const SQRT_PRICE_1000_100: &str = "250541448375047931186413801569";
+    const SQRT_PRICE_10000_100: &str = "792281625142643375935439503360";
+
+    fn u256(value: &str) -> U256 {
+        return U256::from_dec_str(value).unwrap();
+    }
+
+    #[test]
+    fn sqrt_ratio_at_tick_bounds() {
+        assert_eq!(get_sqrt_ratio_at_tick(0), Some(U256::one() << 96));
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK), Some(U256::from(MIN_SQRT_RATIO)));
+        assert_eq!(
+            get_sqrt_ratio_at_tick(MAX_TICK),
+            Some(u256("1461446703485210103287273052203988822378723970342"))
+        );
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK - 1), None);
+        assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK + 1), None);
+    }
+
+    #[test]
+    fn tick_at_sqrt_ratio_round_trips() {
+        for tick in [MIN_TICK, -500_000, -60, -1, 0, 1, 60, 200_000, MAX_TICK - 1] {
+            let sqrt_ratio = get_sqrt_ratio_at_tick(tick).unwrap();
+            assert_eq!(get_tick_at_sqrt_ratio(sqrt_ratio), Some(tick));
+            if tick > MIN_TICK {
+                assert_eq!(get_tick_at_sqrt_ratio(sqrt_ratio - 1), Some(tick - 1));
+            }
+        }
+        assert_eq!(get_tick_at_sqrt_ratio(u256("1461446703485210103287273052203988822378723970341")), Some(MAX_TICK - 1));
+        assert_eq!(get_tick_at_sqrt_ratio(U256::from(MIN_SQRT_RATIO) - 1), None);
+        assert_eq!(get_tick_at_sqrt_ratio(get_sqrt_ratio_at_tick(MAX_TICK).unwrap()), None);
+    }
+
+    #[test]
+    fn swap_step_exact_input_capped_at_target() {
+        let (price, price_target) = (u256(SQRT_PRICE_1_1), u256(SQRT_PRICE_101_100));
+        let amount = I256::exp10(18);
+        let (sqrt_price_next, amount_in, amount_out, fee_amount) =
+            compute_swap_step(price, price_target, 2_000_000_000_000_000_000, amount, 600).unwrap();
+        assert_eq!(sqrt_price_next, price_target);
+        assert_eq!(amount_in, u256("9975124224178055"));
+        assert_eq!(amount_out, u256("9925619580021728"));
+        assert_eq!(fee_amount, u256("5988667735148"));
+    }
+
+    #[test]
+    fn swap_step_exact_output_capped_at_target() {
+        let (price, price_target) = (u256(SQRT_PRICE_1_1), u256(SQRT_PRICE_101_100));
+        let amount = -I256::exp10(18);
+        let (sqrt_price_next, amount_in, amount_out, fee_amount) =
+            compute_swap_step(price, price_target, 2_000_000_000_000_000_000, amount, 600).unwrap();
+        assert_eq!(sqrt_price_next, price_target);
+        assert_eq!(amount_in, u256("9975124224178055"));
+        assert_eq!(amount_out, u256("9925619580021728"));
+        assert_eq!(fee_amount, u256("5988667735148"));
+    }
+
+    #[test]
+    fn swap_step_exact_input_fully_spent() {
+        let (price, price_target) = (u256(SQRT_PRICE_1_1), u256(SQRT_PRICE_1000_100));
+        let amount = I256::exp10(18);
+        let (sqrt_price_next, amount_in, amount_out, fee_amount) =
+            compute_swap_step(price, price_target, 2_000_000_000_000_000_000, amount, 600).unwrap();
+        assert!(sqrt_price_next < price_target);
+        assert_eq!(amount_in, u256("999400000000000000"));
+        assert_eq!(amount_out, u256("666399946655997866"));
+        assert_eq!(fee_amount, u256("600000000000000"));
+        assert_eq!(amount_in + fee_amount, amount.into_raw());
+    }
+
+    #[test]
+    fn swap_step_exact_output_fully_received() {
+        let (price, price_target) = (u256(SQRT_PRICE_1_1), u256(SQRT_PRICE_10000_100));
+        let amount = -I256::exp10(18);
+        let (sqrt_price_next, amount_in, amount_out, fee_amount) =
+            compute_swap_step(price, price_target, 2_000_000_000_000_000_000, amount, 600).unwrap();
+        assert!(sqrt_price_next < price_target);
+        assert_eq!(sqrt_price_next, u256(SQRT_PRICE_1_1) * 2);
+        assert_eq!(amount_in, u256("2000000000000000000"));
+        assert_eq!(amount_out, U256::exp10(18));
+        assert_eq!(fee_amount, u256("1200720432259356"));
+    }
+}
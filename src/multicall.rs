@@ -0,0 +1,125 @@
+use crate::contracts::{Call3, Multicall3};
+use ethers::{
+    abi::Detokenize,
+    contract::ContractCall,
+    providers::{Http, Provider},
+    types::{Address, Bytes},
+};
+use std::{sync::Arc, time};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+
+// calls are batched while they keep coming within the window, batches are
+// bounded to keep aggregate calls under the rpc gas limit of eth_call
+const BATCH_WINDOW: u64 = 5;
+const MAX_BATCH_SIZE: usize = 100;
+
+type Request = (Call3, oneshot::Sender<Result<Bytes, CallError>>);
+
+/// failure of a contract call
+#[derive(Clone, Copy, Debug)]
+pub enum CallError {
+    /// the called contract reverted
+    Reverted,
+
+    /// the request failed or its output could not be decoded
+    Rpc,
+}
+
+/// batcher of contracts read calls, calls made concurrently are grouped and
+/// sent together in multicall3 `aggregate3` calls, a reverted call only
+/// fails on its caller
+pub struct Multicall {
+    sender: UnboundedSender<Request>,
+}
+
+impl Multicall {
+    /// spawn the batching task sending aggregate calls to the multicall3
+    /// contract at `address`
+    pub fn new(address: Address, provider: Arc<Provider<Http>>) -> Multicall {
+        let (sender, receiver) = mpsc::unbounded_channel::<Request>();
+        tokio::spawn(Self::batch_calls(Multicall3::new(address, provider), receiver));
+        return Multicall { sender };
+    }
+
+    /// send `call` in the next batch and decode its output
+    pub async fn call<D: Detokenize>(&self, call: ContractCall<Provider<Http>, D>) -> Result<D, CallError> {
+        let (Some(target), Some(call_data)) = (call.tx.to_addr(), call.tx.data()) else {
+            return Err(CallError::Rpc)
+        };
+        let request = Call3 {
+            target: *target,
+            allow_failure: true,
+            call_data: call_data.clone(),
+        };
+
+        let (sender, receiver) = oneshot::channel::<Result<Bytes, CallError>>();
+        if let Err(_) = self.sender.send((request, sender)) {
+            return Err(CallError::Rpc);
+        }
+        let Ok(return_data) = receiver.await else {
+            return Err(CallError::Rpc)
+        };
+
+        let Ok(tokens) = call.function.decode_output(&return_data?) else {
+            return Err(CallError::Rpc)
+        };
+        let Ok(output) = D::from_tokens(tokens) else {
+            return Err(CallError::Rpc)
+        };
+        return Ok(output);
+    }
+
+    async fn batch_calls(multicall: Multicall3<Provider<Http>>, mut receiver: UnboundedReceiver<Request>) {
+        while let Some(request) = receiver.recv().await {
+            let mut batch = vec![request];
+            let window = tokio::time::sleep(time::Duration::from_millis(BATCH_WINDOW));
+            tokio::pin!(window);
+            while batch.len() < MAX_BATCH_SIZE {
+                tokio::select! {
+                    request = receiver.recv() => {
+                        let Some(request) = request else {
+                            break
+                        };
+                        batch.push(request);
+                    }
+                    _ = &mut window => break,
+                }
+            }
+            tokio::spawn(Self::aggregate(multicall.clone(), batch));
+        }
+    }
+
+    async fn aggregate(multicall: Multicall3<Provider<Http>>, batch: Vec<Request>) {
+        let (calls, senders): (Vec<Call3>, Vec<oneshot::Sender<Result<Bytes, CallError>>>) = batch.into_iter().unzip();
+        let Ok(results) = multicall.aggregate_3(calls).call().await else {
+            println!("error sending multicall batch");
+            senders.into_iter().for_each(|sender| {
+                let _ = sender.send(Err(CallError::Rpc));
+            });
+            return
+        };
+
+        for (sender, (success, return_data)) in senders.into_iter().zip(results) {
+            let return_data = if success { Ok(return_data) } else { Err(CallError::Reverted) };
+            let _ = sender.send(return_data);
+        }
+    }
+}
+
+/// send `call` batched through `multicall` if any, directly otherwise
+pub async fn call<D: Detokenize>(
+    multicall: Option<&Multicall>,
+    call: ContractCall<Provider<Http>, D>,
+) -> Result<D, CallError> {
+    if let Some(multicall) = multicall {
+        return multicall.call(call).await;
+    }
+    match call.call().await {
+        Ok(output) => return Ok(output),
+        Err(err) if err.is_revert() => return Err(CallError::Reverted),
+        Err(_) => return Err(CallError::Rpc),
+    }
+}
@@ -0,0 +1,152 @@
+use crate::assets::Asset;
+use crate::dexs::{DexError, FEE_DENOMINATOR};
+use crate::watchers::Route;
+use ethers::types::{I256, U256};
+use std::fmt;
+
+// golden ratio conjugate scaled by GOLDEN_RATIO_SCALE
+const GOLDEN_RATIO: u64 = 618_034;
+const GOLDEN_RATIO_SCALE: u64 = 1_000_000;
+const MAX_ITERATIONS: usize = 32;
+// stop searching when the interval is smaller than max_amount_in / MIN_INTERVAL_DIVISOR
+const MIN_INTERVAL_DIVISOR: u64 = 10_000;
+
+/// error sizing a trade
+#[derive(Clone, Copy, Debug)]
+pub enum OptimizerError {
+    /// there is nothing to trade, the pool or the lenders have no liquidity
+    NoLiquidity,
+
+    /// no lender can lend the asset or the amount found
+    NoFlashLoan,
+
+    /// the trade could not be quoted
+    Quote(DexError),
+}
+
+impl fmt::Display for OptimizerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimizerError::NoLiquidity => return write!(f, "no liquidity"),
+            OptimizerError::NoFlashLoan => return write!(f, "no flash loan available"),
+            OptimizerError::Quote(err) => return write!(f, "error quoting trade: {:?}", err),
+        }
+    }
+}
+
+impl From<DexError> for OptimizerError {
+    fn from(err: DexError) -> OptimizerError {
+        return OptimizerError::Quote(err);
+    }
+}
+
+/// evaluated trade size of a route, amounts are in `asset_loan` units
+#[derive(Clone, Debug)]
+pub struct TradeSize {
+    /// amount of `asset_loan` borrowed and sold on the buy leg
+    pub amount_in: U256,
+
+    /// amount of `asset_trade` received from the buy leg and sold on the sell leg
+    pub trade_amount: U256,
+
+    /// amount of `asset_loan` received from the sell leg
+    pub amount_out: U256,
+
+    /// `amount_out` minus `amount_in`, flash loan fee and gas cost
+    pub profit: I256,
+}
+
+/// search with a golden-section search the amount of `asset_loan` between 0 and
+/// `max_amount_in` that maximises the net profit of trading it through `route`,
+/// `flash_loan_fee` is in hundredths of a bip of the amount borrowed and `gas_cost` is
+/// expressed in `asset_loan` units
+///
+/// net profit is concave on the amount traded for constant product and
+/// concentrated liquidity pools, price impact grows with the amount
+pub async fn optimize_trade_size(
+    route: &Route,
+    asset_loan: &Asset,
+    asset_trade: &Asset,
+    max_amount_in: U256,
+    flash_loan_fee: u32,
+    gas_cost: U256,
+) -> Result<TradeSize, OptimizerError> {
+    if max_amount_in.is_zero() {
+        return Err(OptimizerError::NoLiquidity);
+    }
+    let min_interval = max_amount_in / MIN_INTERVAL_DIVISOR;
+    let evaluate = |amount_in: U256| {
+        evaluate_trade_size(route, asset_loan, asset_trade, amount_in, flash_loan_fee, gas_cost)
+    };
+
+    let (mut low, mut high) = (U256::zero(), max_amount_in);
+    let (mut x0, mut x1) = (high - golden_section(high - low), low + golden_section(high - low));
+    let (mut size0, mut size1) = (evaluate(x0).await, evaluate(x1).await);
+
+    for _ in 0..MAX_ITERATIONS {
+        if high - low <= min_interval {
+            break;
+        }
+        if profit(&size0) < profit(&size1) {
+            low = x0;
+            (x0, size0) = (x1, size1);
+            x1 = low + golden_section(high - low);
+            size1 = evaluate(x1).await;
+        } else {
+            high = x1;
+            (x1, size1) = (x0, size0);
+            x0 = high - golden_section(high - low);
+            size0 = evaluate(x0).await;
+        }
+    }
+
+    let best = if profit(&size0) > profit(&size1) { size0 } else { size1 };
+    return Ok(best?);
+}
+
+/// quote `amount_in` of `asset_loan` through both legs of `route`, fails if a
+/// quote reverts(e.g amount bigger than pool liquidity)
+pub async fn evaluate_trade_size(
+    route: &Route,
+    asset_loan: &Asset,
+    asset_trade: &Asset,
+    amount_in: U256,
+    flash_loan_fee: u32,
+    gas_cost: U256,
+) -> Result<TradeSize, DexError> {
+    let trade_amount = route
+        .buy_dex
+        .quote_exact_input(&route.buy_quote.pool, asset_loan, asset_trade, amount_in)
+        .await?;
+    let amount_out = route
+        .sell_dex
+        .quote_exact_input(&route.sell_quote.pool, asset_trade, asset_loan, trade_amount)
+        .await?;
+
+    let cost = amount_in + flash_loan_fee_amount(amount_in, flash_loan_fee) + gas_cost;
+    return Ok(TradeSize {
+        amount_in,
+        trade_amount,
+        amount_out,
+        profit: I256::from_raw(amount_out) - I256::from_raw(cost),
+    });
+}
+
+/// profit of an evaluated trade size, sizes that could not be quoted are the
+/// least profitable ones while searching
+#[inline]
+fn profit(trade_size: &Result<TradeSize, DexError>) -> I256 {
+    return trade_size.as_ref().map_or(I256::MIN, |trade_size| trade_size.profit);
+}
+
+/// fee of borrowing `amount`, `fee` is in hundredths of a bip, rounded up
+#[inline]
+pub fn flash_loan_fee_amount(amount: U256, fee: u32) -> U256 {
+    let scale = U256::from(FEE_DENOMINATOR);
+    return (amount * fee + scale - 1) / scale;
+}
+
+#[inline]
+fn golden_section(interval: U256) -> U256 {
+    return interval * GOLDEN_RATIO / GOLDEN_RATIO_SCALE;
+}
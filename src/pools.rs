@@ -0,0 +1,387 @@
+use crate::contracts::{AlgebraPool, UniswapV2Pair, UniswapV3Pool};
+use crate::dexs::get_amount_out;
+use crate::math::{tick_position, ConcentratedLiquidity};
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::{Address, BlockId, Log, H256, I256, U256, U64},
+    utils::keccak256,
+};
+use futures::future::join_all;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+
+// tick bitmap words loaded at each side of the current tick word, a word covers
+// 256 tick spacings(e.g ~29% of price on a 0.05% uniswapV3 pool)
+const TICK_BITMAP_WORDS: i16 = 2;
+
+const SWAP_SIGNATURE: &str = "Swap(address,address,int256,int256,uint160,uint128,int24)";
+const MINT_SIGNATURE: &str = "Mint(address,address,int24,int24,uint128,uint256,uint256)";
+const BURN_SIGNATURE: &str = "Burn(address,int24,int24,uint128,uint256,uint256)";
+const FEE_SIGNATURE: &str = "Fee(uint16)";
+const SYNC_SIGNATURE: &str = "Sync(uint112,uint112)";
+
+/// pool implementation, it defines how the state of a pool is read and synced
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolKind {
+    UniswapV3,
+    Algebra,
+    UniswapV2,
+}
+
+/// error loading the state of pools
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolStateError {
+    /// the block to load the states at could not be read
+    Rpc,
+
+    /// a call reading the state of a pool failed, e.g the pool is of another kind
+    StateCall,
+}
+
+impl fmt::Display for PoolStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoolStateError::Rpc => return write!(f, "rpc error"),
+            PoolStateError::StateCall => return write!(f, "error reading pool state"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum PoolState {
+    ConcentratedLiquidity(ConcentratedLiquidity),
+    ConstantProduct { reserve0: U256, reserve1: U256 },
+}
+
+/// local mirror of the state of pools, loaded from the chain and kept in sync
+/// applying their events so quotes can be computed without calling quoters
+pub struct PoolsState {
+    provider: Arc<Provider<Http>>,
+
+    /// state of every loaded pool with the block it was loaded at,
+    /// events up to that block are already included in it
+    states: RwLock<HashMap<Address, (PoolState, U64)>>,
+
+    /// states are only served while events are being applied
+    synced: AtomicBool,
+}
+
+impl PoolsState {
+    pub fn new(provider: Arc<Provider<Http>>) -> PoolsState {
+        PoolsState {
+            provider,
+            states: RwLock::new(HashMap::new()),
+            synced: AtomicBool::new(false),
+        }
+    }
+
+    /// load the state of `pools` at the latest block, replacing the loaded ones,
+    /// pools whose state can't be read are dropped
+    pub async fn load(&self, pools: &Vec<(Address, PoolKind)>) -> Result<(), PoolStateError> {
+        let Ok(block_number) = self.provider.get_block_number().await else {
+            return Err(PoolStateError::Rpc)
+        };
+        for (address, kind) in pools.iter() {
+            let state = match kind {
+                PoolKind::UniswapV3 => self.load_uniswapv3(*address, block_number).await,
+                PoolKind::Algebra => self.load_algebra(*address, block_number).await,
+                PoolKind::UniswapV2 => self.load_uniswapv2(*address, block_number).await,
+            };
+            let state = match state {
+                Ok(state) => state,
+                Err(err) => {
+                    println!("error loading state of pool {:?}: {err}", address);
+                    self.states.write().unwrap().remove(address);
+                    continue;
+                }
+            };
+            self.states.write().unwrap().insert(*address, (state, block_number));
+        }
+        return Ok(());
+    }
+
+    async fn load_uniswapv3(&self, address: Address, block_number: U64) -> Result<PoolState, PoolStateError> {
+        let pool = UniswapV3Pool::new(address, self.provider.clone());
+        let block = BlockId::from(block_number);
+        let (slot_0, liquidity, tick_spacing, fee) = (
+            pool.slot_0().block(block),
+            pool.liquidity().block(block),
+            pool.tick_spacing().block(block),
+            pool.fee().block(block),
+        );
+        let Ok(((sqrt_price_x96, tick, _, _, _, _, _), liquidity, tick_spacing, fee)) =
+            futures::try_join!(slot_0.call(), liquidity.call(), tick_spacing.call(), fee.call()) else {
+            return Err(PoolStateError::StateCall)
+        };
+
+        let mut state = ConcentratedLiquidity {
+            sqrt_price_x96,
+            tick,
+            liquidity,
+            tick_spacing,
+            fee,
+            ..Default::default()
+        };
+
+        // the bitmap words and then the ticks initialized in them are read concurrently
+        let words_pos = Self::words_around(&state);
+        let words_calls: Vec<_> = words_pos
+            .iter()
+            .map(|word_pos| pool.tick_bitmap(*word_pos).block(block))
+            .collect();
+        let words = join_all(words_calls.iter().map(|call| call.call())).await;
+        let mut ticks = Vec::<i32>::new();
+        for (word_pos, word) in words_pos.into_iter().zip(words) {
+            let Ok(word) = word else {
+                return Err(PoolStateError::StateCall)
+            };
+            state.tick_bitmap.insert(word_pos, word);
+            ticks.extend(Self::initialized_ticks(word_pos, word, tick_spacing));
+        }
+
+        let ticks_calls: Vec<_> = ticks.iter().map(|tick| pool.ticks(*tick).block(block)).collect();
+        let ticks_data = join_all(ticks_calls.iter().map(|call| call.call())).await;
+        for (tick, tick_data) in ticks.into_iter().zip(ticks_data) {
+            let Ok((liquidity_gross, liquidity_net, _, _, _, _, _, _)) = tick_data else {
+                return Err(PoolStateError::StateCall)
+            };
+            state.ticks.insert(tick, (liquidity_gross, liquidity_net));
+        }
+        return Ok(PoolState::ConcentratedLiquidity(state));
+    }
+
+    /// algebra pools store the same state as uniswapV3 ones under other names,
+    /// the fee is dynamic and its changes are notified with `Fee` events
+    async fn load_algebra(&self, address: Address, block_number: U64) -> Result<PoolState, PoolStateError> {
+        let pool = AlgebraPool::new(address, self.provider.clone());
+        let block = BlockId::from(block_number);
+        let (global_state, liquidity, tick_spacing) = (
+            pool.global_state().block(block),
+            pool.liquidity().block(block),
+            pool.tick_spacing().block(block),
+        );
+        let Ok(((sqrt_price_x96, tick, fee, _, _, _, _), liquidity, tick_spacing)) =
+            futures::try_join!(global_state.call(), liquidity.call(), tick_spacing.call()) else {
+            return Err(PoolStateError::StateCall)
+        };
+
+        let mut state = ConcentratedLiquidity {
+            sqrt_price_x96,
+            tick,
+            liquidity,
+            tick_spacing,
+            fee: fee as u32,
+            ..Default::default()
+        };
+
+        let words_pos = Self::words_around(&state);
+        let words_calls: Vec<_> = words_pos
+            .iter()
+            .map(|word_pos| pool.tick_table(*word_pos).block(block))
+            .collect();
+        let words = join_all(words_calls.iter().map(|call| call.call())).await;
+        let mut ticks = Vec::<i32>::new();
+        for (word_pos, word) in words_pos.into_iter().zip(words) {
+            let Ok(word) = word else {
+                return Err(PoolStateError::StateCall)
+            };
+            state.tick_bitmap.insert(word_pos, word);
+            ticks.extend(Self::initialized_ticks(word_pos, word, tick_spacing));
+        }
+
+        let ticks_calls: Vec<_> = ticks.iter().map(|tick| pool.ticks(*tick).block(block)).collect();
+        let ticks_data = join_all(ticks_calls.iter().map(|call| call.call())).await;
+        for (tick, tick_data) in ticks.into_iter().zip(ticks_data) {
+            let Ok((liquidity_total, liquidity_delta, _, _, _, _, _, _)) = tick_data else {
+                return Err(PoolStateError::StateCall)
+            };
+            state.ticks.insert(tick, (liquidity_total, liquidity_delta));
+        }
+        return Ok(PoolState::ConcentratedLiquidity(state));
+    }
+
+    async fn load_uniswapv2(&self, address: Address, block_number: U64) -> Result<PoolState, PoolStateError> {
+        let pair = UniswapV2Pair::new(address, self.provider.clone());
+        let Ok((reserve0, reserve1, _)) = pair.get_reserves().block(BlockId::from(block_number)).call().await else {
+            return Err(PoolStateError::StateCall)
+        };
+        return Ok(PoolState::ConstantProduct {
+            reserve0: U256::from(reserve0),
+            reserve1: U256::from(reserve1),
+        });
+    }
+
+    /// tick bitmap words positions around the current tick of `state`
+    fn words_around(state: &ConcentratedLiquidity) -> Vec<i16> {
+        let (word_pos, _) = tick_position(state.tick.div_euclid(state.tick_spacing));
+        return (word_pos.saturating_sub(TICK_BITMAP_WORDS)..=word_pos.saturating_add(TICK_BITMAP_WORDS)).collect();
+    }
+
+    /// ticks flagged as initialized in the tick bitmap `word` at `word_pos`
+    fn initialized_ticks(word_pos: i16, word: U256, tick_spacing: i32) -> Vec<i32> {
+        return (0..256)
+            .filter(|bit_pos| word.bit(*bit_pos))
+            .map(|bit_pos| ((word_pos as i32) * 256 + bit_pos as i32) * tick_spacing)
+            .collect();
+    }
+
+    /// set whether states are being kept in sync, while not synced quotes
+    /// are not served and callers must fall back to the chain
+    pub fn set_synced(&self, synced: bool) {
+        self.synced.store(synced, Ordering::SeqCst);
+    }
+
+    /// apply the state changes of pools events, removed logs(reorgs) drop
+    /// the pool state until it's loaded again
+    pub fn apply_logs(&self, logs: &Vec<Log>) {
+        let mut states = self.states.write().unwrap();
+        for log in logs.iter() {
+            let Some((state, block_number)) = states.get_mut(&log.address) else {
+                continue
+            };
+            if log.block_number.map_or(false, |log_block| log_block <= *block_number) {
+                continue;
+            }
+            if log.removed == Some(true) || Self::apply_log(state, log).is_none() {
+                println!("pool {:?} state out of sync", log.address);
+                states.remove(&log.address);
+            }
+        }
+    }
+
+    fn apply_log(state: &mut PoolState, log: &Log) -> Option<()> {
+        let signature = log.topics.first()?;
+        let data = log.data.as_ref();
+
+        match state {
+            PoolState::ConcentratedLiquidity(state) => {
+                if *signature == Self::topic(SWAP_SIGNATURE) {
+                    state.sqrt_price_x96 = Self::word(data, 2)?;
+                    state.liquidity = Self::word(data, 3)?.as_u128();
+                    state.tick = I256::from_raw(Self::word(data, 4)?).as_i32();
+                } else if *signature == Self::topic(MINT_SIGNATURE) {
+                    let (tick_lower, tick_upper) = (Self::topic_tick(log, 2)?, Self::topic_tick(log, 3)?);
+                    let amount = Self::word(data, 1)?.as_u128() as i128;
+                    state.modify_position(tick_lower, tick_upper, amount);
+                } else if *signature == Self::topic(BURN_SIGNATURE) {
+                    let (tick_lower, tick_upper) = (Self::topic_tick(log, 2)?, Self::topic_tick(log, 3)?);
+                    let amount = Self::word(data, 0)?.as_u128() as i128;
+                    state.modify_position(tick_lower, tick_upper, -amount);
+                } else if *signature == Self::topic(FEE_SIGNATURE) {
+                    state.fee = Self::word(data, 0)?.as_u32();
+                }
+            }
+            PoolState::ConstantProduct { reserve0, reserve1 } => {
+                if *signature == Self::topic(SYNC_SIGNATURE) {
+                    *reserve0 = Self::word(data, 0)?;
+                    *reserve1 = Self::word(data, 1)?;
+                }
+            }
+        }
+        return Some(());
+    }
+
+    #[inline]
+    fn topic(signature: &str) -> H256 {
+        return H256::from(keccak256(signature));
+    }
+
+    #[inline]
+    fn word(data: &[u8], idx: usize) -> Option<U256> {
+        let word = data.get(idx * 32..(idx + 1) * 32)?;
+        return Some(U256::from_big_endian(word));
+    }
+
+    #[inline]
+    fn topic_tick(log: &Log, idx: usize) -> Option<i32> {
+        let topic = log.topics.get(idx)?;
+        return Some(I256::from_raw(U256::from_big_endian(topic.as_bytes())).as_i32());
+    }
+
+    /// compute the output amount of swapping `amount_in` of `asset_in` to `asset_out`
+    /// in `pool` with its local state, `None` if it's not loaded or not synced
+    pub fn quote_exact_input(&self, pool: Address, asset_in: Address, asset_out: Address, amount_in: U256) -> Option<U256> {
+        if !self.synced.load(Ordering::SeqCst) {
+            return None;
+        }
+        let states = self.states.read().unwrap();
+        let (state, _) = states.get(&pool)?;
+
+        // pools sort their tokens by address, token0 < token1
+        let zero_for_one = asset_in < asset_out;
+        match state {
+            PoolState::ConcentratedLiquidity(state) => {
+                return state.quote_exact_input(amount_in, zero_for_one);
+            }
+            PoolState::ConstantProduct { reserve0, reserve1 } => {
+                let (reserve_in, reserve_out) = if zero_for_one {
+                    (*reserve0, *reserve1)
+                } else {
+                    (*reserve1, *reserve0)
+                };
+                return Some(get_amount_out(amount_in, reserve_in, reserve_out));
+            }
+        }
+    }
+
+    /// current fee of `pool` in its local state
+    pub fn fee(&self, pool: Address) -> Option<u32> {
+        if !self.synced.load(Ordering::SeqCst) {
+            return None;
+        }
+        let states = self.states.read().unwrap();
+        let Some((PoolState::ConcentratedLiquidity(state), _)) = states.get(&pool) else {
+            return None
+        };
+        return Some(state.fee);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::Ierc20;
+    use crate::contracts::UniswapV3Quoter;
+
+    // run with `cargo test -- --ignored`, quotes of the uniswapV3 pool at POOL_ADDRESS
+    // are compared with the ones of the quoter at QUOTER_ADDRESS on RPC_URL
+    fn env(key: &str) -> String {
+        return std::env::var(key).expect(&format!("{key} not set"));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn local_quotes_match_quoter() {
+        let provider = Arc::new(Provider::<Http>::try_from(env("RPC_URL")).unwrap());
+        let address: Address = env("POOL_ADDRESS").parse().unwrap();
+        let quoter = UniswapV3Quoter::new(env("QUOTER_ADDRESS").parse::<Address>().unwrap(), provider.clone());
+        let pool = UniswapV3Pool::new(address, provider.clone());
+        let (token0, token1, fee) = (
+            pool.token_0().call().await.unwrap(),
+            pool.token_1().call().await.unwrap(),
+            pool.fee().call().await.unwrap(),
+        );
+
+        let pools_state = PoolsState::new(provider.clone());
+        pools_state.load(&vec![(address, PoolKind::UniswapV3)]).await.unwrap();
+        pools_state.set_synced(true);
+
+        // a thousandth of the pool balance stays within the loaded tick bitmap words
+        for (asset_in, asset_out) in [(token0, token1), (token1, token0)] {
+            let balance = Ierc20::new(asset_in, provider.clone()).balance_of(address).call().await.unwrap();
+            let amount_in = balance / 1000;
+            let expected = quoter
+                .quote_exact_input_single(asset_in, asset_out, fee, amount_in, U256::zero())
+                .call()
+                .await
+                .unwrap();
+            assert_eq!(pools_state.quote_exact_input(address, asset_in, asset_out, amount_in), Some(expected));
+        }
+    }
+}
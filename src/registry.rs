@@ -0,0 +1,339 @@
+use crate::assets::Asset;
+use crate::db::{PoolRecord, Store, StoreError};
+use crate::dexs::Pool;
+use crate::multicall::{self, CallError, Multicall};
+use crate::pools::PoolKind;
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::{Address, Filter, Log, H256, U256},
+    utils::keccak256,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, RwLock},
+    time,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+// events emitted by the factories when a pool(pair on uniswapV2) is created, tokens
+// are indexed on all of them and uniswapV3 fee tiers too
+const POOL_CREATED_SIGNATURE: &str = "PoolCreated(address,address,uint24,int24,address)";
+const ALGEBRA_POOL_SIGNATURE: &str = "Pool(address,address,address)";
+const PAIR_CREATED_SIGNATURE: &str = "PairCreated(address,address,address,uint256)";
+
+// blocks requested at once when scanning the factories logs, rpc
+// providers limit the range of a logs request
+const LOGS_BLOCK_RANGE: u64 = 2_000;
+
+/// error discovering pools or checking their liquidity
+#[derive(Debug)]
+pub enum RegistryError {
+    /// pools could not be read from or written to the db
+    Db(StoreError),
+
+    /// the latest block or the logs of a factory could not be read
+    Rpc,
+
+    /// the balance of a pool could not be read
+    Balance(CallError),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegistryError::Db(err) => return write!(f, "db error: {err}"),
+            RegistryError::Rpc => return write!(f, "rpc error"),
+            RegistryError::Balance(err) => return write!(f, "error reading pool balance: {:?}", err),
+        }
+    }
+}
+
+impl From<StoreError> for RegistryError {
+    fn from(err: StoreError) -> RegistryError {
+        return RegistryError::Db(err);
+    }
+}
+
+/// factory of a dex, pools of the dex named `dex` are created by it
+#[derive(Clone, Debug)]
+pub struct Factory {
+    pub dex: String,
+    pub address: Address,
+    pub kind: PoolKind,
+}
+
+impl Factory {
+    pub fn new(dex: &str, address: Address, kind: PoolKind) -> Factory {
+        Factory {
+            dex: String::from(dex),
+            address,
+            kind,
+        }
+    }
+}
+
+/// registry of the pools created by the dexs factories, discovered from their
+/// creation events and stored, it serves the pools of the watched assets to the
+/// dexs instead of looking them up in the factories on every tick
+pub struct PoolRegistry {
+    provider: Arc<Provider<Http>>,
+    store: Arc<dyn Store>,
+    factories: Vec<Factory>,
+
+    /// assets pools are served for, pools of other assets are only stored
+    assets: Vec<Asset>,
+
+    /// minimum balance of a loan asset a pool pairing it must hold to be served,
+    /// pools without loan assets can't be valued and are always served
+    min_liquidity: Vec<(Asset, U256)>,
+
+    /// batcher of the contracts calls
+    multicall: Option<Arc<Multicall>>,
+
+    /// last block scanned of each factory
+    scanned_blocks: RwLock<HashMap<Address, u64>>,
+
+    /// pools of the watched assets and the ones of them above the liquidity threshold
+    watched_pools: RwLock<Vec<PoolRecord>>,
+    liquid_pools: RwLock<HashSet<Address>>,
+}
+
+impl PoolRegistry {
+    pub fn new(
+        provider: Arc<Provider<Http>>,
+        store: Arc<dyn Store>,
+        factories: Vec<Factory>,
+        assets: Vec<Asset>,
+    ) -> PoolRegistry {
+        PoolRegistry {
+            provider,
+            store,
+            factories,
+            assets,
+            min_liquidity: vec![],
+            multicall: None,
+            scanned_blocks: RwLock::new(HashMap::new()),
+            watched_pools: RwLock::new(Vec::new()),
+            liquid_pools: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// serve only pools holding at least the minimum balance, in its smallest
+    /// unit, of each of the `min_liquidity` loan assets they pair
+    pub fn with_min_liquidity(mut self, min_liquidity: Vec<(Asset, U256)>) -> PoolRegistry {
+        self.min_liquidity = min_liquidity;
+        return self;
+    }
+
+    pub fn with_multicall(mut self, multicall: Arc<Multicall>) -> PoolRegistry {
+        self.multicall = Some(multicall);
+        return self;
+    }
+
+    /// load the stored pools and scan the factories from `start_block`, or from
+    /// the block of the last stored pool of each factory if it's later
+    pub async fn load(&self, start_block: u64) -> Result<(), RegistryError> {
+        let pools = self.store.get_pools().await?;
+
+        {
+            let mut scanned_blocks = self.scanned_blocks.write().unwrap();
+            for factory in self.factories.iter() {
+                let last_block = pools
+                    .iter()
+                    .filter(|pool| pool.dex == factory.dex)
+                    .map(|pool| pool.block)
+                    .max()
+                    .unwrap_or_default();
+                // the block of the last pool is scanned again, pools are replaced by address
+                scanned_blocks.insert(factory.address, start_block.max(last_block).saturating_sub(1));
+            }
+        }
+        *self.watched_pools.write().unwrap() = pools.into_iter().filter(|pool| self.is_watched(pool)).collect();
+
+        self.sync().await?;
+        return Ok(());
+    }
+
+    /// scan the factories from their last scanned block to the latest one, store
+    /// the created pools and check the liquidity of the watched ones, returns
+    /// the pools served since the previous sync
+    pub async fn sync(&self) -> Result<Vec<Address>, RegistryError> {
+        let Ok(latest_block) = self.provider.get_block_number().await else {
+            return Err(RegistryError::Rpc)
+        };
+        let latest_block = latest_block.as_u64();
+
+        for factory in self.factories.iter() {
+            let scanned_block = self.scanned_blocks.read().unwrap().get(&factory.address).cloned().unwrap_or_default();
+            let mut from_block = scanned_block + 1;
+            while from_block <= latest_block {
+                let to_block = latest_block.min(from_block + LOGS_BLOCK_RANGE - 1);
+                let pools = self.scan(factory, from_block, to_block).await?;
+                self.store.put_pools(&pools).await?;
+                if !pools.is_empty() {
+                    println!("{} {} pools created up to block {to_block}", pools.len(), factory.dex);
+                }
+
+                {
+                    let mut watched_pools = self.watched_pools.write().unwrap();
+                    for pool in pools.into_iter().filter(|pool| self.is_watched(pool)) {
+                        if !watched_pools.iter().any(|watched_pool| watched_pool.address == pool.address) {
+                            watched_pools.push(pool);
+                        }
+                    }
+                }
+                self.scanned_blocks.write().unwrap().insert(factory.address, to_block);
+                from_block = to_block + 1;
+            }
+        }
+
+        return Ok(self.check_liquidity().await);
+    }
+
+    /// sync the registry every `interval` milliseconds so new pools are served
+    /// as soon as they are created, newly served pools are sent through `updates`
+    /// to be watched, it stops when the receiver is dropped
+    pub async fn follow(self: Arc<Self>, interval: u64, updates: UnboundedSender<Vec<Address>>) {
+        loop {
+            tokio::time::sleep(time::Duration::from_millis(interval)).await;
+            match self.sync().await {
+                Ok(pools) if pools.is_empty() => (),
+                Ok(pools) => {
+                    if let Err(_) = updates.send(pools) {
+                        return;
+                    }
+                }
+                Err(err) => println!("error syncing pools registry: {err}"),
+            }
+        }
+    }
+
+    /// get the pools of the pair `asset0`/`asset1` created by the factory at
+    /// `factory` above the liquidity threshold
+    pub fn get_pools(&self, factory: Address, asset0: Address, asset1: Address) -> Vec<Pool> {
+        let Some(factory) = self.factories.iter().find(|known_factory| known_factory.address == factory) else {
+            return vec![]
+        };
+        let dex = factory.dex.as_str();
+        let liquid_pools = self.liquid_pools.read().unwrap();
+        return self
+            .watched_pools
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|pool| pool.dex == dex && Self::is_pair(pool, asset0, asset1) && liquid_pools.contains(&pool.address))
+            .map(|pool| Pool {
+                address: pool.address,
+                fee_tier: pool.fee_tier,
+            })
+            .collect();
+    }
+
+    /// count the pools of the pair `asset0`/`asset1` above the liquidity threshold in every dex
+    pub fn count_pools(&self, asset0: Address, asset1: Address) -> usize {
+        let liquid_pools = self.liquid_pools.read().unwrap();
+        return self
+            .watched_pools
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|pool| Self::is_pair(pool, asset0, asset1) && liquid_pools.contains(&pool.address))
+            .count();
+    }
+
+    /// get the pools created by `factory` between `from_block` and `to_block`
+    async fn scan(&self, factory: &Factory, from_block: u64, to_block: u64) -> Result<Vec<PoolRecord>, RegistryError> {
+        let signature = match factory.kind {
+            PoolKind::UniswapV3 => POOL_CREATED_SIGNATURE,
+            PoolKind::Algebra => ALGEBRA_POOL_SIGNATURE,
+            PoolKind::UniswapV2 => PAIR_CREATED_SIGNATURE,
+        };
+        let filter = Filter::new()
+            .address(factory.address)
+            .topic0(H256::from(keccak256(signature)))
+            .from_block(from_block)
+            .to_block(to_block);
+
+        let Ok(logs) = self.provider.get_logs(&filter).await else {
+            println!("error getting {} factory logs from block {from_block}", factory.dex);
+            return Err(RegistryError::Rpc)
+        };
+        return Ok(logs.iter().filter_map(|log| Self::decode(factory, log)).collect());
+    }
+
+    /// decode the pool created in `log`, the pool address is the last word of the
+    /// data of uniswapV3 logs and the first one of algebra and uniswapV2 ones
+    fn decode(factory: &Factory, log: &Log) -> Option<PoolRecord> {
+        let (token0, token1) = (Address::from(*log.topics.get(1)?), Address::from(*log.topics.get(2)?));
+        let data = log.data.as_ref();
+        let (address, fee_tier) = match factory.kind {
+            PoolKind::UniswapV3 => {
+                let fee_tier = U256::from_big_endian(log.topics.get(3)?.as_bytes()).as_u32();
+                (Address::from_slice(data.get(44..64)?), Some(fee_tier))
+            }
+            PoolKind::Algebra | PoolKind::UniswapV2 => (Address::from_slice(data.get(12..32)?), None),
+        };
+
+        return Some(PoolRecord {
+            address,
+            dex: factory.dex.clone(),
+            token0,
+            token1,
+            fee_tier,
+            block: log.block_number?.as_u64(),
+        });
+    }
+
+    /// check the balances of the loan assets of the watched pools against the
+    /// liquidity threshold, balances are read concurrently, returns the pools
+    /// that weren't above it in the previous check, pools whose balance can not
+    /// be read keep their previous liquidity
+    async fn check_liquidity(&self) -> Vec<Address> {
+        let watched_pools = self.watched_pools.read().unwrap().clone();
+        let pools_liquidity = futures::future::join_all(watched_pools.iter().map(|pool| self.is_liquid(pool))).await;
+
+        let mut previous_liquid_pools = self.liquid_pools.write().unwrap();
+        let mut liquid_pools = HashSet::<Address>::with_capacity(watched_pools.len());
+        for (pool, liquid) in watched_pools.iter().zip(pools_liquidity) {
+            let liquid = match liquid {
+                Ok(liquid) => liquid,
+                Err(err) => {
+                    println!("{err} of pool {:?}, keeping its previous liquidity", pool.address);
+                    previous_liquid_pools.contains(&pool.address)
+                }
+            };
+            if liquid {
+                liquid_pools.insert(pool.address);
+            }
+        }
+        let new_pools = liquid_pools.difference(&previous_liquid_pools).cloned().collect();
+        *previous_liquid_pools = liquid_pools;
+        return new_pools;
+    }
+
+    async fn is_liquid(&self, pool: &PoolRecord) -> Result<bool, RegistryError> {
+        for (asset, min_balance) in self.min_liquidity.iter() {
+            if asset.address() != pool.token0 && asset.address() != pool.token1 {
+                continue;
+            }
+            let balance = match multicall::call(self.multicall.as_deref(), asset.contract.balance_of(pool.address)).await {
+                Ok(balance) => balance,
+                Err(err) => return Err(RegistryError::Balance(err)),
+            };
+            return Ok(balance >= *min_balance);
+        }
+        return Ok(true);
+    }
+
+    /// whether both tokens of `pool` are watched assets
+    fn is_watched(&self, pool: &PoolRecord) -> bool {
+        let watched = |token: Address| self.assets.iter().any(|asset| asset.address() == token);
+        return watched(pool.token0) && watched(pool.token1);
+    }
+
+    #[inline]
+    fn is_pair(pool: &PoolRecord, asset0: Address, asset1: Address) -> bool {
+        return (pool.token0, pool.token1) == (asset0, asset1) || (pool.token0, pool.token1) == (asset1, asset0);
+    }
+}
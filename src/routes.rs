@@ -0,0 +1,217 @@
+use crate::amounts::Amount;
+use crate::assets::Asset;
+use crate::dexs::{Dex, DexError, Pool};
+use ethers::types::{Address, U256};
+use std::{collections::HashMap, sync::Arc};
+
+// direct loan->trade->loan round trips are evaluated by watchers
+const MIN_CYCLE_HOPS: usize = 3;
+const MAX_CYCLE_HOPS: usize = 4;
+
+/// graph of assets connected by the pools of the dexs where they can be swapped
+pub struct TokenGraph {
+    assets: Vec<Asset>,
+
+    /// pools of every pair, keyed by the pair addresses sorted
+    pools: HashMap<(Address, Address), Vec<(Arc<dyn Dex>, Pool)>>,
+}
+
+impl TokenGraph {
+    /// build the graph discovering the pools of every pair of `assets` in `dexs`
+    pub async fn new(assets: Vec<Asset>, dexs: &Vec<Arc<dyn Dex>>) -> TokenGraph {
+        let mut pools = HashMap::<(Address, Address), Vec<(Arc<dyn Dex>, Pool)>>::new();
+        for (idx, asset0) in assets.iter().enumerate() {
+            for asset1 in assets[idx + 1..].iter() {
+                let mut pair_pools = Vec::<(Arc<dyn Dex>, Pool)>::new();
+                for dex in dexs.iter() {
+                    let Ok(dex_pools) = dex.get_pools(asset0, asset1).await else {
+                        continue
+                    };
+                    pair_pools.extend(dex_pools.into_iter().map(|pool| (dex.clone(), pool)));
+                }
+                if !pair_pools.is_empty() {
+                    pools.insert(Self::pair_key(asset0, asset1), pair_pools);
+                }
+            }
+        }
+
+        return TokenGraph { assets, pools };
+    }
+
+    /// find cycles that start and end in `start` going through
+    /// between `MIN_CYCLE_HOPS` and `MAX_CYCLE_HOPS` pools
+    pub fn find_cycles(&self, start: &Asset) -> Vec<Cycle> {
+        let mut cycles = Vec::<Cycle>::new();
+        let mut path = vec![start.clone()];
+        self.search_cycles(start, &mut path, &mut cycles);
+        return cycles;
+    }
+
+    fn search_cycles(&self, start: &Asset, path: &mut Vec<Asset>, cycles: &mut Vec<Cycle>) {
+        let Some(last) = path.last().cloned() else {
+            return
+        };
+
+        for asset in self.assets.iter() {
+            let Some(pools) = self.get_pools(&last, asset) else {
+                continue
+            };
+
+            if asset.address() == start.address() {
+                if path.len() >= MIN_CYCLE_HOPS {
+                    let mut cycle_path = path.clone();
+                    cycle_path.push(asset.clone());
+                    cycles.push(self.cycle_from_path(cycle_path));
+                }
+                continue;
+            }
+
+            let visited = path.iter().any(|visited| visited.address() == asset.address());
+            if visited || pools.is_empty() || path.len() >= MAX_CYCLE_HOPS {
+                continue;
+            }
+
+            path.push(asset.clone());
+            self.search_cycles(start, path, cycles);
+            path.pop();
+        }
+    }
+
+    fn cycle_from_path(&self, path: Vec<Asset>) -> Cycle {
+        let hops = path
+            .windows(2)
+            .map(|pair| self.get_pools(&pair[0], &pair[1]).cloned().unwrap_or_default())
+            .collect();
+        return Cycle { path, hops };
+    }
+
+    #[inline]
+    fn get_pools(&self, asset0: &Asset, asset1: &Asset) -> Option<&Vec<(Arc<dyn Dex>, Pool)>> {
+        return self.pools.get(&Self::pair_key(asset0, asset1));
+    }
+
+    #[inline]
+    fn pair_key(asset0: &Asset, asset1: &Asset) -> (Address, Address) {
+        if asset0.address() < asset1.address() {
+            return (asset0.address(), asset1.address());
+        } else {
+            return (asset1.address(), asset0.address());
+        }
+    }
+}
+
+/// cyclic route that swaps the first asset of `path` through every
+/// asset of it back to the first one, e.g USDC->WETH->WMATIC->USDC
+pub struct Cycle {
+    path: Vec<Asset>,
+
+    /// candidate pools of each hop, hop `idx` swaps `path[idx]` to `path[idx + 1]`
+    hops: Vec<Vec<(Arc<dyn Dex>, Pool)>>,
+}
+
+impl Cycle {
+    pub async fn watch(&self) -> Result<(), DexError> {
+        let start = &self.path[0];
+        let in_amount = Amount::one(start.decimals())?;
+
+        let (out_amount, pools, _) = match self.quote(in_amount.raw).await {
+            Ok(quote) => quote,
+            Err(err) => {
+                println!("could not quote cycle {}", self.name());
+                return Err(err);
+            }
+        };
+
+        let validated_out_amount = match Self::validate(&self.path, &pools, in_amount.raw).await {
+            Ok(validated_out_amount) => validated_out_amount,
+            Err(err) => {
+                println!("could not validate cycle {}", self.name());
+                return Err(err);
+            }
+        };
+        if validated_out_amount != out_amount {
+            println!("cycle {} quotes mismatch: {out_amount} != {validated_out_amount}", self.name());
+            return Ok(());
+        }
+
+        // cycles are only reported, the arbitrageur contract trades two legs routes so
+        // they are not sized nor simulated and their gas is not estimated, quoted
+        // outputs are net of the pools fees but the profit is before gas
+        if out_amount > in_amount.raw {
+            let out_amount = Amount::new(out_amount, start.decimals());
+            let dexs_names: Vec<String> = pools.iter().map(|(dex, _)| dex.name()).collect();
+            println!(
+                "cycle {} profitable before gas through {}: {in_amount} -> {out_amount}",
+                self.name(),
+                dexs_names.join(",")
+            );
+        }
+
+        return Ok(());
+    }
+
+    /// quote `amount_in` hop by hop picking in each one the pool with the best
+    /// output amount, returns the final amount, the pools used and their fees
+    pub async fn quote(&self, amount_in: U256) -> Result<(U256, Vec<(Arc<dyn Dex>, Pool)>, Vec<u32>), DexError> {
+        let mut amount = amount_in;
+        let mut pools = Vec::<(Arc<dyn Dex>, Pool)>::with_capacity(self.hops.len());
+        let mut fees = Vec::<u32>::with_capacity(self.hops.len());
+        for (idx, hop) in self.hops.iter().enumerate() {
+            let (asset_in, asset_out) = (&self.path[idx], &self.path[idx + 1]);
+            let mut best: Option<(U256, u32, &(Arc<dyn Dex>, Pool))> = None;
+            let mut error = DexError::PoolNotFound;
+            for candidate in hop.iter() {
+                let quote = candidate
+                    .0
+                    .quote_exact_input_with_fee(&candidate.1, asset_in, asset_out, amount)
+                    .await;
+                let (out_amount, fee) = match quote {
+                    Ok(quote) => quote,
+                    Err(err) => {
+                        error = err;
+                        continue;
+                    }
+                };
+                if best.map_or(true, |(best_amount, _, _)| out_amount > best_amount) {
+                    best = Some((out_amount, fee, candidate));
+                }
+            }
+            let Some((out_amount, fee, candidate)) = best else {
+                return Err(error)
+            };
+            amount = out_amount;
+            pools.push(candidate.clone());
+            fees.push(fee);
+        }
+        return Ok((amount, pools, fees));
+    }
+
+    /// quote `amount_in` again through `pools` using path quotes, consecutive
+    /// hops on the same dex are quoted in a single call
+    async fn validate(path: &Vec<Asset>, pools: &Vec<(Arc<dyn Dex>, Pool)>, amount_in: U256) -> Result<U256, DexError> {
+        let mut amount = amount_in;
+        let mut start = 0;
+        while start < pools.len() {
+            let dex = &pools[start].0;
+            let mut end = start + 1;
+            while end < pools.len() && Arc::ptr_eq(&pools[end].0, dex) {
+                end += 1;
+            }
+            let segment_pools: Vec<Pool> = pools[start..end].iter().map(|(_, pool)| pool.clone()).collect();
+            let segment_path: Vec<Asset> = path[start..=end].to_vec();
+            amount = dex.quote_exact_input_path(&segment_pools, &segment_path, amount).await?;
+            start = end;
+        }
+        return Ok(amount);
+    }
+
+    /// get the candidate pools of every hop
+    pub fn get_pools(&self) -> Vec<(Arc<dyn Dex>, Pool)> {
+        return self.hops.iter().flatten().cloned().collect();
+    }
+
+    pub fn name(&self) -> String {
+        let symbols: Vec<String> = self.path.iter().map(|asset| asset.symbol()).collect();
+        return symbols.join("->");
+    }
+}
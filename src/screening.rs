@@ -0,0 +1,398 @@
+use crate::amounts::Amount;
+use crate::assets::Asset;
+use crate::contracts::{Call3, Multicall3};
+use ethers::{
+    providers::{Http, Middleware, Provider, RawCall},
+    types::{spoof, transaction::eip2718::TypedTransaction, Address, Bytes, TransactionRequest, H256, U256},
+    utils::{id, keccak256},
+};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc};
+
+// storage slots searched for the balances mapping, upgradeable tokens place it
+// after the storage gaps of their base contracts(e.g slot 51 on openzeppelin ones)
+const MAX_BALANCES_SLOT: u64 = 128;
+const BALANCES_SLOTS_BATCH: usize = 16;
+
+// balance written to the probed slots, unlikely to be an actual balance
+const PROBE_BALANCE: u64 = 0x5a5a_5a5a_5a5a;
+
+// namespaced storage of openzeppelin V5 erc20, balances are its first member
+const ERC20_NAMESPACE: &str = "openzeppelin.storage.ERC20";
+
+// slots of the implementation of eip-1967 proxies, the beacon of beacon proxies
+// and the implementation of the legacy openzeppelin(zeppelinos) proxies
+const IMPLEMENTATION_SLOT_ID: &str = "eip1967.proxy.implementation";
+const BEACON_SLOT_ID: &str = "eip1967.proxy.beacon";
+const LEGACY_IMPLEMENTATION_SLOT_ID: &str = "org.zeppelinos.proxy.implementation";
+const BEACON_IMPLEMENTATION_SIGNATURE: &str = "implementation()";
+
+// functions of tokens whose transfers can be stopped by an admin
+const PAUSE_SIGNATURES: [&str; 2] = ["paused()", "pause()"];
+const BLACKLIST_SIGNATURES: [&str; 5] = [
+    "isBlacklisted(address)",
+    "isBlackListed(address)",
+    "blacklist(address)",
+    "addBlackList(address)",
+    "freeze(address)",
+];
+
+// function selectors are pushed by the solidity dispatcher with the shortest PUSH
+// fitting them, selectors with leading zero bytes are pushed with PUSH1 to PUSH3
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+
+/// behaviour of a token that makes trading it risky
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenRisk {
+    /// transfers deliver less than the amount sent
+    FeeOnTransfer,
+
+    /// balances are not read from a storage mapping, as on tokens
+    /// computing them from shares
+    Rebasing,
+
+    /// `transfer` does not return `true`
+    NonStandardReturn,
+
+    /// a plain transfer reverted
+    TransferReverted,
+
+    /// transfers can be paused
+    Pausable,
+
+    /// accounts can be blacklisted or frozen
+    Blacklist,
+
+    /// the token logic can be upgraded
+    Proxy,
+
+    /// one unit of the token does not fit in 256 bits
+    UnsupportedDecimals,
+}
+
+impl fmt::Display for TokenRisk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenRisk::FeeOnTransfer => return write!(f, "fee on transfer"),
+            TokenRisk::Rebasing => return write!(f, "rebasing"),
+            TokenRisk::NonStandardReturn => return write!(f, "non standard return"),
+            TokenRisk::TransferReverted => return write!(f, "transfer reverted"),
+            TokenRisk::Pausable => return write!(f, "pausable"),
+            TokenRisk::Blacklist => return write!(f, "blacklist"),
+            TokenRisk::Proxy => return write!(f, "proxy"),
+            TokenRisk::UnsupportedDecimals => return write!(f, "unsupported decimals"),
+        }
+    }
+}
+
+/// error screening a token, the chain could not be read so its risks are unknown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreeningError {
+    /// code or storage of an account could not be read
+    Rpc,
+
+    /// a simulated call failed as a request, reverted transfers are risks instead
+    Simulation,
+
+    /// the output of a call could not be decoded
+    MalformedOutput,
+}
+
+impl fmt::Display for ScreeningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScreeningError::Rpc => return write!(f, "rpc error"),
+            ScreeningError::Simulation => return write!(f, "simulation failed"),
+            ScreeningError::MalformedOutput => return write!(f, "malformed call output"),
+        }
+    }
+}
+
+/// screen tokens for risky behaviours, transfers are simulated with `eth_call`
+/// on top of the latest block of the node of `provider`, a local fork of the chain
+/// or the rpc node itself, the balances of the tokens are set with state overrides
+/// of the call so the simulations never change the state of the node
+///
+/// transfers are sent from the multicall3 contract, it batches the balance
+/// reads around the transfer in the same call
+pub struct Screener {
+    provider: Arc<Provider<Http>>,
+    multicall: Multicall3<Provider<Http>>,
+}
+
+impl Screener {
+    pub fn new(provider: Arc<Provider<Http>>, multicall: Address) -> Screener {
+        Screener {
+            provider: provider.clone(),
+            multicall: Multicall3::new(multicall, provider),
+        }
+    }
+
+    /// get the risks of `asset`, fails if the chain could not be read
+    pub async fn screen(&self, asset: &Asset) -> Result<Vec<TokenRisk>, ScreeningError> {
+        let mut risks = Vec::<TokenRisk>::new();
+
+        // selectors are looked up in the implementation of proxies
+        let mut code = self.get_code(asset.address()).await?;
+        if let Some(implementation) = self.get_implementation(asset.address()).await? {
+            risks.push(TokenRisk::Proxy);
+            code = self.get_code(implementation).await?;
+        }
+        if PAUSE_SIGNATURES.iter().any(|signature| has_selector(&code, signature)) {
+            risks.push(TokenRisk::Pausable);
+        }
+        if BLACKLIST_SIGNATURES.iter().any(|signature| has_selector(&code, signature)) {
+            risks.push(TokenRisk::Blacklist);
+        }
+
+        match self.find_balance_key(asset).await? {
+            Some(balance_key) => risks.extend(self.simulate_transfer(asset, balance_key).await?),
+            None => risks.push(TokenRisk::Rebasing),
+        }
+        return Ok(risks);
+    }
+
+    /// simulate transferring 1 unit of `asset` from the multicall contract, its
+    /// balance is overridden at `balance_key` of the token storage
+    async fn simulate_transfer(&self, asset: &Asset, balance_key: H256) -> Result<Vec<TokenRisk>, ScreeningError> {
+        let (sender, recipient) = (self.multicall.address(), Address::repeat_byte(0x5c));
+        let Ok(amount) = Amount::one(asset.decimals()) else {
+            return Ok(vec![TokenRisk::UnsupportedDecimals])
+        };
+        let amount = amount.raw;
+
+        let calls: Vec<Call3> = [
+            asset.contract.balance_of(recipient).calldata(),
+            asset.contract.transfer(recipient, amount).calldata(),
+            asset.contract.balance_of(recipient).calldata(),
+            asset.contract.balance_of(sender).calldata(),
+        ]
+        .into_iter()
+        .map(|call_data| Call3 {
+            target: asset.address(),
+            allow_failure: true,
+            call_data: call_data.unwrap_or_default(),
+        })
+        .collect();
+
+        let mut state = spoof::state();
+        state.account(asset.address()).store(balance_key, to_word(amount));
+        let aggregate_call = self.multicall.aggregate_3(calls);
+        let Ok(results) = aggregate_call.call_raw().state(&state).await else {
+            return Err(ScreeningError::Simulation)
+        };
+        let [(_, balance_before), (transferred, transfer_output), (_, balance_after), (_, sender_balance)] =
+            &results[..] else {
+            return Err(ScreeningError::MalformedOutput)
+        };
+
+        if !transferred {
+            return Ok(vec![TokenRisk::TransferReverted]);
+        }
+        let mut risks = Vec::<TokenRisk>::new();
+        if decode_word(transfer_output) != Some(U256::one()) {
+            risks.push(TokenRisk::NonStandardReturn);
+        }
+        let (Some(balance_before), Some(balance_after), Some(sender_balance)) = (
+            decode_word(balance_before),
+            decode_word(balance_after),
+            decode_word(sender_balance),
+        ) else {
+            return Err(ScreeningError::MalformedOutput)
+        };
+        if balance_after.saturating_sub(balance_before) != amount || !sender_balance.is_zero() {
+            risks.push(TokenRisk::FeeOnTransfer);
+        }
+        return Ok(risks);
+    }
+
+    /// find the storage key of the balance of the multicall contract in `asset`, the
+    /// balances mapping is searched on the first slots with the key layouts of
+    /// solidity and vyper and on the openzeppelin V5 namespace, probes are batched
+    async fn find_balance_key(&self, asset: &Asset) -> Result<Option<H256>, ScreeningError> {
+        let holder = self.multicall.address();
+        let mut slots: Vec<U256> = (0..MAX_BALANCES_SLOT).map(U256::from).collect();
+        slots.push(namespace_slot(ERC20_NAMESPACE));
+        let keys: Vec<H256> = slots
+            .into_iter()
+            .flat_map(|slot| [mapping_key(holder, slot, false), mapping_key(holder, slot, true)])
+            .collect();
+
+        for batch in keys.chunks(BALANCES_SLOTS_BATCH) {
+            let probes = join_all(batch.iter().map(|key| self.probe_balance_key(asset, holder, *key))).await;
+            for (key, probe) in batch.iter().zip(probes) {
+                if probe? {
+                    return Ok(Some(*key));
+                }
+            }
+        }
+        return Ok(None);
+    }
+
+    /// whether overriding `key` of the storage of `asset` sets the balance of `holder`
+    async fn probe_balance_key(&self, asset: &Asset, holder: Address, key: H256) -> Result<bool, ScreeningError> {
+        let mut state = spoof::state();
+        state.account(asset.address()).store(key, to_word(U256::from(PROBE_BALANCE)));
+        let balance_call = asset.contract.balance_of(holder);
+        let Ok(balance) = balance_call.call_raw().state(&state).await else {
+            return Err(ScreeningError::Simulation)
+        };
+        return Ok(balance == U256::from(PROBE_BALANCE));
+    }
+
+    /// get the implementation of `address` if it's a proxy
+    async fn get_implementation(&self, address: Address) -> Result<Option<Address>, ScreeningError> {
+        let implementation_slots = [
+            eip1967_slot(IMPLEMENTATION_SLOT_ID),
+            H256::from(keccak256(LEGACY_IMPLEMENTATION_SLOT_ID)),
+        ];
+        for slot in implementation_slots {
+            let implementation = self.get_storage_address(address, slot).await?;
+            if implementation != Address::zero() {
+                return Ok(Some(implementation));
+            }
+        }
+
+        let beacon = self.get_storage_address(address, eip1967_slot(BEACON_SLOT_ID)).await?;
+        if beacon == Address::zero() {
+            return Ok(None);
+        }
+        let call: TypedTransaction = TransactionRequest::new()
+            .to(beacon)
+            .data(Bytes::from(id(BEACON_IMPLEMENTATION_SIGNATURE).to_vec()))
+            .into();
+        let Ok(output) = self.provider.call(&call, None).await else {
+            return Err(ScreeningError::Rpc)
+        };
+        let Some(implementation) = decode_word(&output) else {
+            return Err(ScreeningError::MalformedOutput)
+        };
+        return Ok(Some(Address::from(to_word(implementation))));
+    }
+
+    async fn get_storage_address(&self, address: Address, slot: H256) -> Result<Address, ScreeningError> {
+        let Ok(word) = self.provider.get_storage_at(address, slot, None).await else {
+            return Err(ScreeningError::Rpc)
+        };
+        return Ok(Address::from(word));
+    }
+
+    async fn get_code(&self, address: Address) -> Result<Bytes, ScreeningError> {
+        let Ok(code) = self.provider.get_code(address, None).await else {
+            return Err(ScreeningError::Rpc)
+        };
+        return Ok(code);
+    }
+}
+
+/// whether the function `signature` is dispatched in `code`
+#[inline]
+fn has_selector(code: &Bytes, signature: &str) -> bool {
+    return pushes_selector(code, id(signature));
+}
+
+/// whether `selector` is pushed in `code`, it's a heuristic: the opcodes are walked
+/// so push data is not taken for instructions, but a selector pushed for another
+/// reason is a false positive and dispatchers not pushing selectors(e.g vyper
+/// jump tables) are missed
+fn pushes_selector(code: &Bytes, selector: [u8; 4]) -> bool {
+    let start = selector.iter().position(|byte| *byte != 0).unwrap_or(3);
+    let selector = &selector[start..];
+    let push = PUSH1 + (selector.len() - 1) as u8;
+
+    let mut idx = 0;
+    while idx < code.len() {
+        let opcode = code[idx];
+        if !(PUSH1..=PUSH32).contains(&opcode) {
+            idx += 1;
+            continue;
+        }
+        let data_len = (opcode - PUSH1 + 1) as usize;
+        if opcode == push && code.get(idx + 1..idx + 1 + data_len) == Some(selector) {
+            return true;
+        }
+        idx += 1 + data_len;
+    }
+    return false;
+}
+
+/// storage key of `holder` in the mapping at `slot`, keys are hashed
+/// before the slot by solidity and after it by vyper
+fn mapping_key(holder: Address, slot: U256, vyper: bool) -> H256 {
+    let (mut holder_word, mut slot_word) = ([0u8; 32], [0u8; 32]);
+    holder_word[12..].copy_from_slice(holder.as_bytes());
+    slot.to_big_endian(&mut slot_word);
+    let data = match vyper {
+        false => [holder_word, slot_word].concat(),
+        true => [slot_word, holder_word].concat(),
+    };
+    return H256::from(keccak256(data));
+}
+
+/// slot of the eip-1967 `slot_id`, keccak256(slot_id) - 1
+fn eip1967_slot(slot_id: &str) -> H256 {
+    return to_word(U256::from_big_endian(&keccak256(slot_id)) - 1);
+}
+
+/// slot of the erc-7201 `namespace`, keccak256(keccak256(namespace) - 1) & ~0xff
+fn namespace_slot(namespace: &str) -> U256 {
+    let slot = U256::from_big_endian(&keccak256(namespace)) - 1;
+    return U256::from_big_endian(&keccak256(to_word(slot))) & !U256::from(0xff);
+}
+
+#[inline]
+fn to_word(value: U256) -> H256 {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    return H256::from(word);
+}
+
+#[inline]
+fn decode_word(data: &Bytes) -> Option<U256> {
+    let word = data.get(..32)?;
+    return Some(U256::from_big_endian(word));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::Ierc20;
+
+    #[tokio::test]
+    async fn unit_overflowing_decimals_are_a_risk() {
+        // the transfer is not simulated, nothing listens on the provider
+        let provider = Arc::new(Provider::<Http>::try_from("http://localhost:1").unwrap());
+        let screener = Screener::new(provider.clone(), Address::repeat_byte(0xca));
+        let address = Address::repeat_byte(0x01);
+        let asset = Asset {
+            contract: Ierc20::new(address, provider),
+            address,
+            symbol: String::from("WIDE"),
+            decimals: 78,
+            risks: None,
+            screening_error: None,
+        };
+        let risks = screener.simulate_transfer(&asset, H256::zero()).await;
+        assert_eq!(risks, Ok(vec![TokenRisk::UnsupportedDecimals]));
+    }
+
+    #[test]
+    fn find_pushed_selectors() {
+        // DUP1 PUSH4 0x5c975abb(paused()) EQ
+        let code = Bytes::from(vec![0x80, 0x63, 0x5c, 0x97, 0x5a, 0xbb, 0x14]);
+        assert!(has_selector(&code, "paused()"));
+        assert!(!has_selector(&code, "pause()"));
+
+        // selectors with leading zero bytes are pushed with shorter pushes, DUP1 PUSH2 0x1234 EQ
+        let code = Bytes::from(vec![0x80, 0x61, 0x12, 0x34, 0x14]);
+        assert!(pushes_selector(&code, [0x00, 0x00, 0x12, 0x34]));
+        assert!(!pushes_selector(&code, [0x00, 0x12, 0x34, 0x00]));
+
+        // PUSH4 inside the data of a PUSH5 is not an instruction
+        let code = Bytes::from(vec![0x64, 0x63, 0x5c, 0x97, 0x5a, 0xbb]);
+        assert!(!has_selector(&code, "paused()"));
+    }
+}
@@ -1,19 +1,49 @@
 use crate::assets::Asset;
-use crate::dexs::Dex;
+use crate::caller::{Caller, CallerError, Simulation, SlippageGuard, Status};
+use crate::amounts::{Amount, Price};
+use crate::db::{Decision, OpportunityRecord, Store, TradeRecord};
+use crate::dexs::{Dex, DexError, Pool, Quote, Side, FEE_DENOMINATOR};
+use crate::flashloans::{self, FlashLoan, FlashLoanProvider};
+use crate::optimizer::{self, OptimizerError, TradeSize};
 use ethers::{
     providers::{Http, Provider},
-    types::Address,
+    types::{Address, TransactionReceipt, I256, U256},
+    providers::Middleware,
+    utils::format_units,
+};
+use futures::future::join_all;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
-use std::sync::Arc;
 use tabled::{Table, Tabled};
 
-const AAVE_FEE: f64 = 0.05 ;
-const UNISWAPV3_FEE : f64 = 0.05 ;
-const QUICKSWAPV3_FEE : f64 = 0.10 ;
+const BPS: u32 = 10_000;
+
+/// trade route, buy `asset_trade` with `asset_loan` in the pool of `buy_dex`
+/// quoted by `buy_quote` and sell it back in the pool of `sell_dex` quoted
+/// by `sell_quote`, both dexs can be the same with different pools
+#[derive(Clone)]
+pub struct Route {
+    pub buy_dex: Arc<dyn Dex>,
+    pub buy_quote: Quote,
+    pub sell_dex: Arc<dyn Dex>,
+    pub sell_quote: Quote,
+}
+
+/// trade oportunity detected by a watcher
+#[derive(Clone)]
+pub struct Opportunity {
+    pub route: Route,
+    pub trade_size: TradeSize,
 
-enum Direction {
-    UniswapV3ToQuickswapV3 = 0,
-    QuickswapV3ToUniswapV3 = 1,
+    /// lender `trade_size.amount_in` is borrowed from
+    pub flash_loan: FlashLoan,
+
+    /// simulation of the arbitrage call, `None` until simulated
+    pub simulation: Option<Simulation>,
 }
 
 #[derive(Tabled)]
@@ -33,12 +63,17 @@ struct TableData {
     #[tabled(rename = "loan asset balance")]
     pool_loan_asset_balance: f64,
 
-    // #[tabled(display_with = "Dex::get_trade_asset_sym")]
-    #[tabled(rename = "trade asset out amount")]
-    pool_trade_asset_out_amount: f64,
+    side: Side,
+
+    /// amount of the spent asset, loan asset on buys and trade asset on sells
+    #[tabled(rename = "in amount")]
+    in_amount: f64,
 
-    #[tabled(rename = "loan asset out amount")]
-    pool_loan_asset_out_amount: f64,
+    #[tabled(rename = "out amount")]
+    out_amount: f64,
+
+    /// price of the trade asset in the loan asset
+    price: f64,
 }
 
 impl TableData {
@@ -46,53 +81,44 @@ impl TableData {
         dex_name: String,
         pool_name: String,
         pool_address: String,
-        pool_trade_asset_balance: f64,
-        pool_loan_asset_balance: f64,
-        pool_trade_asset_out_amount: f64,
-        pool_loan_asset_out_amount: f64,
+        pool_data: &PoolData,
+        quote: &Quote,
     ) -> TableData {
         TableData {
             dex_name,
             pool_name,
             pool_address,
-            pool_trade_asset_balance,
-            pool_loan_asset_balance,
-            pool_trade_asset_out_amount,
-            pool_loan_asset_out_amount,
+            pool_trade_asset_balance: pool_data.trade_balance.to_f64(),
+            pool_loan_asset_balance: pool_data.loan_balance.to_f64(),
+            side: quote.side,
+            in_amount: quote.in_amount.to_f64(),
+            out_amount: quote.out_amount.to_f64(),
+            price: quote.price.to_f64(),
         }
     }
 }
 
 struct PoolData {
-    pub trade_balance: f64,
-    pub loan_balance: f64,
+    pub trade_balance: Amount,
+    pub loan_balance: Amount,
     pub address: Address,
 }
 
-impl PoolData {
-    pub fn from_pool_balance_out(tuple: (f64, f64, Address)) -> PoolData {
-        return PoolData {
-            trade_balance: tuple.0,
-            loan_balance: tuple.1,
-            address: tuple.2,
-        };
-    }
-}
-
 /// wacth price of dynamic price asset `asset_trade` and a
-/// static price asset `asset_loan` in dexes `dex0` and `dex1`
-/// if exist a trade oportunity request a flash loan of on
-/// `asset_loan` to trade `asset_trade`
+/// static price asset `asset_loan` in dexes `dexs`
+/// if exist a trade oportunity request a flash loan of
+/// `asset_loan` to the cheapest of `flash_loan_providers` to trade `asset_trade`
 ///
 /// trade goes like this
 /// loan `asset_loan`
-/// buy `asset_trade` in one dex and sell it in another
+/// buy `asset_trade` in the dex pool where is cheaper and sell it
+/// in the dex pool where is more expensive
 /// get `asset_loan` back
 pub struct Watcher {
     provider: Arc<Provider<Http>>,
 
     /// arbitrageur that will execute trades when detect a trade oportunity
-    // arbitrageur_address: Arbitrageur<Arc<Provider<Http>>>,
+    caller: Arc<Caller>,
 
     /// dynamic/variable price asset
     asset_trade: Asset,
@@ -100,182 +126,620 @@ pub struct Watcher {
     /// static price asset
     asset_loan: Asset,
 
-    dex0: Dex,
-    dex1: Dex,
+    /// wrapped native asset, gas is paid on it
+    asset_native: Asset,
+
+    /// minimum net profit of a trade after fees and gas, in the smallest unit
+    /// of `asset_native`
+    min_net_profit: U256,
+
+    dexs: Vec<Arc<dyn Dex>>,
+
+    flash_loan_providers: Vec<Arc<dyn FlashLoanProvider>>,
+
+    /// maximum share of the balances of a pool a trade leg can swap, in basis points
+    max_pool_share_bps: u32,
+
+    /// tolerated decrease of the simulated trade outputs, in basis points
+    slippage_tolerance_bps: u32,
+
+    /// store the evaluated opportunities and the sent trades are recorded in
+    store: Option<Arc<dyn Store>>,
 }
 
 impl Watcher {
 
-    pub fn new(
-        asset_trade: Asset,
-        asset_loan: Asset,
-        dex0: Dex,
-        dex1: Dex,
-        // arbitrageur: Address,
-        provider: Arc<Provider<Http>>,
-    ) -> Watcher {
-        Watcher {
-            asset_trade,
-            asset_loan,
-            dex0,
-            dex1,
-            provider,
-            // arbitrageur: Arbitrageur::new(arbitrageur,&provider)
-        }
-    }
-
     pub fn from_pairs(
         assets: (Asset, Asset),
-        dexs: (Dex, Dex),
-        // arbitrageur: Arbitrageur<Arc<Provider<Http>>>,
+        asset_native: Asset,
+        min_net_profit: U256,
+        dexs: Vec<Arc<dyn Dex>>,
+        flash_loan_providers: Vec<Arc<dyn FlashLoanProvider>>,
+        caller: Arc<Caller>,
         provider: Arc<Provider<Http>>,
     ) -> Watcher {
         Watcher {
             asset_trade: assets.0,
             asset_loan: assets.1,
-            dex1: dexs.1,
-            dex0: dexs.0,
+            asset_native,
+            min_net_profit,
+            dexs,
+            flash_loan_providers,
             provider,
-            // arbitrageur
+            caller,
+            max_pool_share_bps: BPS,
+            slippage_tolerance_bps: 0,
+            store: None,
         }
     }
 
-    pub async fn watch(&self) -> Result<(), ()> {
-        let Ok((asset_trade_out_amount_dex0,asset_trade_price_dex0)) = self.dex0.check_assets_price(&self.asset_trade, &self.asset_loan).await else {
-            println!("could not get price of assets in dex0");
-            return Err(())
-        };
-        let Ok((asset_trade_out_amount_dex1,asset_trade_price_dex1)) = self.dex1.check_assets_price(&self.asset_trade, &self.asset_loan).await else {
-            println!("could not get price of assets in dex1");
-            return Err(())
-        };
+    pub fn with_max_pool_share_bps(mut self, max_pool_share_bps: u32) -> Watcher {
+        self.max_pool_share_bps = max_pool_share_bps;
+        return self;
+    }
 
-        let direction = self.test_trade(
-            &asset_trade_out_amount_dex0,
-            &asset_trade_price_dex0,
-            &asset_trade_out_amount_dex1,
-            &asset_trade_price_dex1
-        );
+    pub fn with_slippage_tolerance_bps(mut self, slippage_tolerance_bps: u32) -> Watcher {
+        self.slippage_tolerance_bps = slippage_tolerance_bps;
+        return self;
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn Store>) -> Watcher {
+        self.store = Some(store);
+        return self;
+    }
 
-        let failed_state = String::from("failed");
-        let state = if let Some(dir) = direction {
-            self.trade(dir).await.unwrap_or(failed_state);
-        } else {
-            failed_state;
+    pub async fn watch(&self) -> Result<(), DexError> {
+        let quotes = match self.get_quotes().await {
+            Ok(quotes) => quotes,
+            Err(err) => {
+                println!("could not get price of assets in at least two dex pools");
+                return Err(err);
+            }
         };
 
-        self.show(
-            &asset_trade_out_amount_dex0,
-            &asset_trade_price_dex0,
-            &asset_trade_out_amount_dex1,
-            &asset_trade_price_dex1,
-        ).await ;
+        if let Some(route) = Self::select_route(&quotes) {
+            let mut opportunity = None;
+            let outcome = self.evaluate(&route, &mut opportunity).await;
+            match &outcome {
+                Ok(state) | Err(state) => println!("{state}"),
+            }
+            self.record_opportunity(&route, opportunity.as_ref(), &outcome).await;
+        }
+
+        self.show(&quotes).await;
 
         return Ok(());
     }
 
-    fn test_trade(&self,dex0_out_amount: &f64,price0: &f64,dex1_out_amount: &f64, price1: &f64) -> Option<Direction> {
+    /// test `route` and trade it if it's profitable once sized and simulated, returns
+    /// the trade state if traded or the reason it was skipped, `opportunity` is set
+    /// once the trade is sized
+    async fn evaluate(&self, route: &Route, opportunity: &mut Option<Opportunity>) -> Result<String, String> {
+        Self::test_trade(route, &self.asset_trade, &self.asset_loan).await?;
+        println!("{} to {} tested trade", route.buy_dex.name(), route.sell_dex.name());
 
-        if price0 > price1 && Self::calc_roi(&dex1_out_amount,&dex0_out_amount) {
+        let (trade_size, flash_loan) = match self.optimize_trade_size(route).await {
+            Ok(sized_trade) => sized_trade,
+            Err(err) => return Err(format!("failed optimizing trade size: {err}")),
+        };
+        let opportunity = opportunity.insert(Opportunity {
+            route: route.clone(),
+            trade_size,
+            flash_loan,
+            simulation: None,
+        });
+        if opportunity.trade_size.profit <= I256::zero() {
+            return Err(String::from("not profitable at any trade size"));
+        }
+        if !self.test_liquidity(route, &opportunity.trade_size).await {
+            return Err(String::from("trade size too big for pools liquidity"));
+        }
 
-            println!("quick to uni tested trade");
-            return Some(Direction::QuickswapV3ToUniswapV3);
+        match self.simulate(opportunity).await {
+            Ok(true) => return self.trade(opportunity).await,
+            Ok(false) => return Err(String::from("not profitable in simulation")),
+            Err(_) => return Err(String::from("failed simulating trade")),
+        }
+    }
 
-        } else if price1 > price0 &&  Self::calc_roi(&dex0_out_amount,&dex1_out_amount) {
+    /// get the pools of the pair in every dex
+    pub async fn get_pools(&self) -> Vec<(Arc<dyn Dex>, Pool)> {
+        let mut pools = Vec::<(Arc<dyn Dex>, Pool)>::new();
+        for dex in self.dexs.iter() {
+            let Ok(dex_pools) = dex.get_pools(&self.asset_trade, &self.asset_loan).await else {
+                continue
+            };
+            pools.extend(dex_pools.into_iter().map(|pool| (dex.clone(), pool)));
+        }
+        return pools;
+    }
 
-            println!("uni to quick tested trade");
-            return Some(Direction::UniswapV3ToQuickswapV3);
+    /// quote buying `asset_trade` with `asset_loan` and selling it back in every pool
+    /// of every dex, dexs are quoted concurrently, fails if less than two pools are quoted
+    async fn get_quotes(&self) -> Result<Vec<(Arc<dyn Dex>, Quote)>, DexError> {
+        let dexs_sides: Vec<(&Arc<dyn Dex>, Side)> = self
+            .dexs
+            .iter()
+            .flat_map(|dex| [(dex, Side::Buy), (dex, Side::Sell)])
+            .collect();
+        let dexs_quotes = join_all(
+            dexs_sides
+                .iter()
+                .map(|(dex, side)| dex.check_assets_prices(&self.asset_trade, &self.asset_loan, *side)),
+        )
+        .await;
+
+        let mut quotes = Vec::<(Arc<dyn Dex>, Quote)>::new();
+        let mut error = DexError::PoolNotFound;
+        for ((dex, _), dex_quotes) in dexs_sides.into_iter().zip(dexs_quotes) {
+            let dex_quotes = match dex_quotes {
+                Ok(dex_quotes) => dex_quotes,
+                Err(err) => {
+                    println!("could not get price of assets in {}: {:?}", dex.name(), err);
+                    error = err;
+                    continue;
+                }
+            };
+            quotes.extend(dex_quotes.into_iter().map(|quote| (dex.clone(), quote)));
+        }
 
-        } else {
+        let mut pools: Vec<Address> = quotes.iter().map(|(_, quote)| quote.pool.address).collect();
+        pools.sort();
+        pools.dedup();
+        if pools.len() < 2 {
+            return Err(error);
+        }
+        return Ok(quotes);
+    }
 
-            return None;
+    /// pick the pool where `asset_trade` is cheaper to buy and the one, other than
+    /// it, where is more expensive to sell, the route between them is tested
+    /// by `test_trade`
+    fn select_route(quotes: &Vec<(Arc<dyn Dex>, Quote)>) -> Option<Route> {
+        let buy = quotes
+            .iter()
+            .filter(|(_, quote)| quote.side == Side::Buy)
+            .max_by_key(|(_, quote)| quote.out_amount)?;
+        let sell = quotes
+            .iter()
+            .filter(|(_, quote)| quote.side == Side::Sell && quote.pool.address != buy.1.pool.address)
+            .max_by_key(|(_, quote)| quote.out_amount)?;
+
+        return Some(Route {
+            buy_dex: buy.0.clone(),
+            buy_quote: buy.1.clone(),
+            sell_dex: sell.0.clone(),
+            sell_quote: sell.1.clone(),
+        });
+    }
 
+    /// test the sell price of `route` is above its buy price and buying `asset_trade`
+    /// with 1 unit of `asset_loan` on the buy leg and selling back what is bought on
+    /// the sell leg is profitable, returns the reason the route is rejected
+    async fn test_trade(route: &Route, asset_trade: &Asset, asset_loan: &Asset) -> Result<(), String> {
+        let (buy, sell) = (&route.buy_quote, &route.sell_quote);
+        if sell.price.partial_cmp(&buy.price) != Some(Ordering::Greater) {
+            return Err(String::from("sell price not above buy price"));
         }
 
+        let Ok(out_amount) = route
+            .sell_dex
+            .quote_exact_input(&sell.pool, asset_trade, asset_loan, buy.out_amount.raw)
+            .await else {
+            return Err(String::from("failed quoting sell leg"))
+        };
+        let out_amount = Amount::new(out_amount, asset_loan.decimals());
+        // quoted outputs are net of the pools fees, gas and flash loan fee
+        // are accounted once the trade size is known
+        let gas_cost = Amount::new(U256::zero(), asset_loan.decimals());
+        if !Self::calc_roi(&buy.in_amount, &out_amount, &gas_cost, &0) {
+            return Err(String::from("not profitable buying 1 unit"));
+        }
+        return Ok(());
     }
 
-    async fn trade(&self,direction: Direction) -> Result<String, ()> {
+    /// search the amount of `asset_loan` to borrow that maximises the net profit of
+    /// `route`, bounded by the buy pool `asset_loan` liquidity and the flash loans
+    /// liquidity, and the cheapest lender able to lend it
+    async fn optimize_trade_size(&self, route: &Route) -> Result<(TradeSize, FlashLoan), OptimizerError> {
+        let (pool_liquidity, _) = route
+            .buy_dex
+            .get_pool_liquidity(&route.buy_quote.pool, &self.asset_loan, &self.asset_trade)
+            .await?;
+
+        // pools of the route are locked while trading, they can't lend
+        let excluded = vec![route.buy_quote.pool.address, route.sell_quote.pool.address];
+        let flash_loans = flashloans::get_flash_loans(&self.flash_loan_providers, &self.asset_loan, &excluded).await;
+        let (Some(min_fee), Some(max_liquidity)) = (
+            flash_loans.iter().map(|flash_loan| flash_loan.fee).min(),
+            flash_loans.iter().map(|flash_loan| flash_loan.liquidity).max(),
+        ) else {
+            return Err(OptimizerError::NoFlashLoan)
+        };
 
-        let Ok((
-            dex0_pool_data,
-            dex1_pool_data
-        )) = self.get_pool_data().await else {
+        // the trade is sized at the lowest fee, it's evaluated again if the
+        // cheapest lender of the amount found charges more
+        // gas cost does not depend on the trade size, it's subtracted once simulated
+        let trade_size = optimizer::optimize_trade_size(
+            route,
+            &self.asset_loan,
+            &self.asset_trade,
+            pool_liquidity.min(max_liquidity),
+            min_fee,
+            U256::zero(),
+        )
+        .await?;
+        let Some(flash_loan) = flashloans::cheapest(&flash_loans, trade_size.amount_in) else {
+            return Err(OptimizerError::NoFlashLoan)
+        };
+        if flash_loan.fee == min_fee {
+            return Ok((trade_size, flash_loan));
+        }
+
+        let trade_size = optimizer::evaluate_trade_size(
+            route,
+            &self.asset_loan,
+            &self.asset_trade,
+            trade_size.amount_in,
+            flash_loan.fee,
+            U256::zero(),
+        )
+        .await?;
+        return Ok((trade_size, flash_loan));
+    }
+
+    /// test `trade_size` does not swap more than `max_pool_share_bps` of the balances
+    /// of the pools of `route` on either leg and show the price impact of each leg,
+    /// the price paid or received compared with the 1 unit quote of the leg
+    async fn test_liquidity(&self, route: &Route, trade_size: &TradeSize) -> bool {
+        let Ok((buy_pool_data, sell_pool_data)) = self.get_route_pool_data(route).await else {
             println!("err getting pool data");
-            return Err(())
+            return false
         };
 
-        match direction {
-            Direction::UniswapV3ToQuickswapV3 => {
-                // call_arbitrageur(baseAsset,quoteAsset,quoteAssetAmount,direction,fee) ;
-                return Ok(String::from("ok: uni to quick"));
+        // loan asset is swapped to trade asset on the buy leg and back on the sell leg
+        let buy_within_share = self.within_share(trade_size.amount_in, buy_pool_data.loan_balance)
+            && self.within_share(trade_size.trade_amount, buy_pool_data.trade_balance);
+        let sell_within_share = self.within_share(trade_size.trade_amount, sell_pool_data.trade_balance)
+            && self.within_share(trade_size.amount_out, sell_pool_data.loan_balance);
+        if !buy_within_share {
+            println!("buy leg exceeds {} bps of pool {:?} balances", self.max_pool_share_bps, buy_pool_data.address);
+        }
+        if !sell_within_share {
+            println!("sell leg exceeds {} bps of pool {:?} balances", self.max_pool_share_bps, sell_pool_data.address);
+        }
+
+        let trade_amount = Amount::new(trade_size.trade_amount, self.asset_trade.decimals());
+        let (buy_price, sell_price) = (
+            Price::new(trade_amount, Amount::new(trade_size.amount_in, self.asset_loan.decimals())),
+            Price::new(trade_amount, Amount::new(trade_size.amount_out, self.asset_loan.decimals())),
+        );
+        let (buy_impact, sell_impact) = (
+            buy_price.deviation_bps(&route.buy_quote.price),
+            sell_price.deviation_bps(&route.sell_quote.price),
+        );
+        println!(
+            "price impact: buy leg {} bps, sell leg {} bps",
+            buy_impact.map_or(String::from("unknown"), |impact| impact.to_string()),
+            sell_impact.map_or(String::from("unknown"), |impact| impact.to_string())
+        );
+
+        return buy_within_share && sell_within_share;
+    }
+
+    /// minimum outputs of `trade_size` and `profit` within `slippage_tolerance_bps`
+    fn slippage_guard(&self, trade_size: &TradeSize, profit: U256) -> SlippageGuard {
+        let tolerated = U256::from(BPS.saturating_sub(self.slippage_tolerance_bps));
+        return SlippageGuard {
+            buy_amount_out_minimum: trade_size.trade_amount * tolerated / BPS,
+            sell_amount_out_minimum: trade_size.amount_out * tolerated / BPS,
+            min_profit: profit * tolerated / BPS,
+        };
+    }
+
+    /// whether `amount` is at most `max_pool_share_bps` of `balance`
+    #[inline]
+    fn within_share(&self, amount: U256, balance: Amount) -> bool {
+        return amount.full_mul(U256::from(BPS)) <= balance.raw.full_mul(U256::from(self.max_pool_share_bps));
+    }
+
+    /// simulate the arbitrage call of `opportunity` on top of the pending block,
+    /// attach the result to it and return if it is profitable
+    async fn simulate(&self, opportunity: &mut Opportunity) -> Result<bool, ()> {
+        let simulation = self
+            .caller
+            .simulate(
+                &self.asset_loan,
+                &self.asset_trade,
+                &opportunity.route,
+                &opportunity.trade_size,
+                &opportunity.flash_loan,
+                // the profit is guarded once simulated
+                &self.slippage_guard(&opportunity.trade_size, U256::zero()),
+            )
+            .await;
+        let simulation = match simulation {
+            Ok(simulation) => simulation,
+            Err(err) => {
+                println!("error simulating arbitrage: {err}");
+                return Err(());
             }
-            Direction::QuickswapV3ToUniswapV3 => {
-                return Ok(String::from("ok: quick to uni"));
+        };
+
+        match &simulation.revert_reason {
+            Some(reason) => println!("simulation reverted: {reason}"),
+            None => println!(
+                "simulation: profit {} {}, gas used {}",
+                format_units(simulation.profit, self.asset_loan.decimals()).unwrap_or_default(),
+                self.asset_loan.symbol(),
+                simulation.gas_used
+            ),
+        }
+        if let Some(trace) = &simulation.trace {
+            println!("simulation trace: {}", serde_json::to_string(trace).unwrap_or_default());
+        }
+
+        let profitable = simulation.is_profitable() && self.test_net_profit(opportunity, &simulation).await?;
+        opportunity.simulation = Some(simulation);
+        return Ok(profitable);
+    }
+
+    /// test the roi of `opportunity` subtracting the cost of the gas used by its
+    /// `simulation` at current EIP-1559 fees and check the net profit is above
+    /// `min_net_profit`
+    async fn test_net_profit(&self, opportunity: &Opportunity, simulation: &Simulation) -> Result<bool, ()> {
+        let Ok((max_fee_per_gas, _)) = self.provider.estimate_eip1559_fees(None).await else {
+            println!("error estimating gas fees");
+            return Err(())
+        };
+        let gas_cost = self.native_to_loan(simulation.gas_used * max_fee_per_gas).await?;
+        let min_net_profit = self.native_to_loan(self.min_net_profit).await?;
+
+        let decimals = self.asset_loan.decimals();
+        let trade_size = &opportunity.trade_size;
+        let flash_loan_fee = optimizer::flash_loan_fee_amount(trade_size.amount_in, opportunity.flash_loan.fee);
+        let net_profit = I256::from_raw(trade_size.amount_out)
+            - I256::from_raw(trade_size.amount_in + flash_loan_fee + gas_cost);
+        println!(
+            "gas cost {} {sym}, net profit {} {sym}",
+            Amount::new(gas_cost, decimals),
+            format_units(net_profit, decimals).unwrap_or_default(),
+            sym = self.asset_loan.symbol()
+        );
+
+        let (in_amount, out_amount, gas_cost) = (
+            Amount::new(trade_size.amount_in, decimals),
+            Amount::new(trade_size.amount_out, decimals),
+            Amount::new(gas_cost, decimals),
+        );
+        // the flash loan fee is not included in the sell leg output, unlike the pools ones
+        return Ok(Self::calc_roi(&in_amount, &out_amount, &gas_cost, &opportunity.flash_loan.fee)
+            && net_profit >= I256::from_raw(min_net_profit));
+    }
+
+    /// convert `amount` of `asset_native` to `asset_loan` at the best price of the dexs
+    async fn native_to_loan(&self, amount: U256) -> Result<U256, ()> {
+        if amount.is_zero() || self.asset_native.address() == self.asset_loan.address() {
+            return Ok(amount);
+        }
+
+        let mut best_amount: Option<U256> = None;
+        for dex in self.dexs.iter() {
+            let Ok(pools) = dex.get_pools(&self.asset_native, &self.asset_loan).await else {
+                continue
+            };
+            for pool in pools.iter() {
+                let Ok(out_amount) = dex.quote_exact_input(pool, &self.asset_native, &self.asset_loan, amount).await else {
+                    continue
+                };
+                if best_amount.map_or(true, |best_amount| out_amount > best_amount) {
+                    best_amount = Some(out_amount);
+                }
             }
-            _ => return Err(()),
+        }
+
+        let Some(loan_amount) = best_amount else {
+            println!("could not price {} in {}", self.asset_native.symbol(), self.asset_loan.symbol());
+            return Err(())
+        };
+        return Ok(loan_amount);
+    }
+
+    /// send the arbitrage call of `opportunity` and record it, returns the trade
+    /// state if sent or the reason it was not
+    async fn trade(&self, opportunity: &Opportunity) -> Result<String, String> {
+        let (route, trade_size) = (&opportunity.route, &opportunity.trade_size);
+        let Ok((
+            buy_pool_data,
+            sell_pool_data
+        )) = self.get_route_pool_data(route).await else {
+            return Err(String::from("err getting pool data"))
+        };
+
+        println!(
+            "trading {} {} borrowed from {:?} {} {:?} to {} {:?}",
+            format_units(trade_size.amount_in, self.asset_loan.decimals()).unwrap_or_default(),
+            self.asset_loan.symbol(),
+            opportunity.flash_loan.lender,
+            route.buy_dex.name(),
+            buy_pool_data.address,
+            route.sell_dex.name(),
+            sell_pool_data.address
+        );
+
+        // quote the trade again at the latest block, it's not sent if the
+        // price moved beyond the tolerance since it was simulated
+        let simulated_profit = opportunity.simulation.as_ref().map_or(U256::zero(), |simulation| simulation.profit);
+        let guard = self.slippage_guard(trade_size, simulated_profit);
+        let Ok(latest_trade_size) = optimizer::evaluate_trade_size(
+            route,
+            &self.asset_loan,
+            &self.asset_trade,
+            trade_size.amount_in,
+            opportunity.flash_loan.fee,
+            U256::zero(),
+        )
+        .await else {
+            return Err(String::from("failed quoting trade at latest block"))
+        };
+        if latest_trade_size.trade_amount < guard.buy_amount_out_minimum
+            || latest_trade_size.amount_out < guard.sell_amount_out_minimum
+        {
+            return Err(format!("price moved beyond {} bps", self.slippage_tolerance_bps));
+        }
+
+        let (receipt, status) = match self.call_arbitrageur(route, trade_size, &opportunity.flash_loan, &guard).await {
+            Ok(traded) => traded,
+            Err(err) => return Err(format!("failed sending trade: {err}")),
+        };
+        self.record_trade(opportunity, &receipt).await;
+        match status {
+            Status::NotExecuted => return Ok(String::from("not executed")),
+            Status::Profit(profit) => return Ok(format!("ok: profit {profit} {}", self.asset_loan.symbol())),
+            Status::Loss(loss) => return Ok(format!("ok: loss {loss} {}", self.asset_loan.symbol())),
         }
     }
 
-    async fn show(
+    /// record the evaluation of `route` with the `outcome` of `watch`, `opportunity`
+    /// is the route sized and simulated if it got that far
+    async fn record_opportunity(
         &self,
-        out_amount_dex0: &f64,
-        price_dex0: &f64,
-        out_amount_dex1: &f64,
-        price_dex1: &f64,
+        route: &Route,
+        opportunity: Option<&Opportunity>,
+        outcome: &Result<String, String>,
     ) {
-        let (asset_trade_sym, asset_loan_sym) = self.get_asset_syms();
-        let (dex0_name, dex1_name) = self.get_dexs_names();
-        let pool_name = format!("{asset_trade_sym}/{asset_loan_sym}");
+        let Some(store) = &self.store else {
+            return
+        };
 
-        let Ok((
-            dex0_pool_data,
-            dex1_pool_data
-        )) = self.get_pool_data().await else {
-            println!("error getting pool data");
+        let block = self.provider.get_block_number().await.map_or(0, |block| block.as_u64());
+        let pools_fees = route.buy_quote.fee + route.sell_quote.fee;
+        let (fees, amount_in, profit, roi_bps) = match opportunity {
+            Some(Opportunity { trade_size, flash_loan, .. }) => {
+                let roi_bps = I256::try_from(trade_size.amount_in)
+                    .ok()
+                    .and_then(|amount_in| trade_size.profit.checked_mul(I256::from(BPS))?.checked_div(amount_in))
+                    .and_then(|roi_bps| i64::try_from(roi_bps).ok());
+                (pools_fees + flash_loan.fee, Some(trade_size.amount_in), Some(trade_size.profit), roi_bps)
+            }
+            None => (pools_fees, None, None, None),
+        };
+        let simulation = opportunity.and_then(|opportunity| opportunity.simulation.as_ref());
+        let (decision, reason) = match outcome {
+            Ok(_) => (Decision::Traded, None),
+            Err(reason) => (Decision::Skipped, Some(reason.clone())),
+        };
+
+        let opportunity = OpportunityRecord {
+            timestamp: unix_time(),
+            block,
+            asset_trade: self.asset_trade.address(),
+            asset_loan: self.asset_loan.address(),
+            buy_dex: route.buy_dex.name(),
+            buy_pool: route.buy_quote.pool.address,
+            sell_dex: route.sell_dex.name(),
+            sell_pool: route.sell_quote.pool.address,
+            buy_quote: route.buy_quote.out_amount.raw,
+            sell_quote: route.sell_quote.out_amount.raw,
+            fees,
+            amount_in,
+            profit,
+            roi_bps,
+            simulated_profit: simulation.map(|simulation| simulation.profit),
+            gas_used: simulation.map(|simulation| simulation.gas_used),
+            revert_reason: simulation.and_then(|simulation| simulation.revert_reason.clone()),
+            decision,
+            reason,
+        };
+        if let Err(err) = store.put_opportunity(&opportunity).await {
+            println!("could not record opportunity: {err}");
+        }
+    }
+
+    /// record the arbitrage transaction of `opportunity` mined in `receipt`
+    async fn record_trade(&self, opportunity: &Opportunity, receipt: &TransactionReceipt) {
+        let Some(store) = &self.store else {
             return
         };
 
-        let table = Table::new(vec![
-            TableData::new(
-                dex0_name.to_string(),
-                pool_name.to_string(),
-                dex0_pool_data.address.to_string(),
-                dex0_pool_data.trade_balance.clone(),
-                dex0_pool_data.loan_balance.clone(),
-                out_amount_dex0.clone(),
-                price_dex0.clone(),
-            ),
-            TableData::new(
-                dex1_name.clone(),
+        let trade = TradeRecord {
+            timestamp: unix_time(),
+            block: receipt.block_number.map_or(0, |block| block.as_u64()),
+            tx_hash: receipt.transaction_hash,
+            asset_trade: self.asset_trade.address(),
+            asset_loan: self.asset_loan.address(),
+            buy_dex: opportunity.route.buy_dex.name(),
+            sell_dex: opportunity.route.sell_dex.name(),
+            amount_in: opportunity.trade_size.amount_in,
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            gas_price: receipt.effective_gas_price.unwrap_or_default(),
+            profit: self.caller.balance_change(receipt, &self.asset_loan),
+        };
+        if let Err(err) = store.put_trade(&trade).await {
+            println!("could not record trade: {err}");
+        }
+    }
+
+    async fn show(&self, quotes: &Vec<(Arc<dyn Dex>, Quote)>) {
+        let (asset_trade_sym, asset_loan_sym) = self.get_asset_syms();
+        let pool_name = format!("{asset_trade_sym}/{asset_loan_sym}");
+
+        // pools are quoted on both sides, the data of each one is fetched once
+        let mut pools = Vec::<(&Arc<dyn Dex>, &Pool)>::new();
+        for (dex, quote) in quotes.iter() {
+            if !pools.iter().any(|(_, pool)| pool.address == quote.pool.address) {
+                pools.push((dex, &quote.pool));
+            }
+        }
+        let pools_data = join_all(pools.iter().map(|(dex, pool)| self.get_pool_data(dex, pool))).await;
+        let mut pools_data_by_address = HashMap::<Address, PoolData>::with_capacity(pools.len());
+        for ((dex, pool), pool_data) in pools.into_iter().zip(pools_data) {
+            match pool_data {
+                Ok(pool_data) => {
+                    pools_data_by_address.insert(pool.address, pool_data);
+                }
+                Err(err) => println!("error getting data of {} pool {:?}: {:?}", dex.name(), pool.address, err),
+            }
+        }
+
+        let mut rows = Vec::<TableData>::with_capacity(quotes.len());
+        for (dex, quote) in quotes.iter() {
+            let Some(pool_data) = pools_data_by_address.get(&quote.pool.address) else {
+                continue
+            };
+            rows.push(TableData::new(
+                dex.name(),
                 pool_name.clone(),
-                dex1_pool_data.address.to_string(),
-                dex1_pool_data.trade_balance.clone(),
-                dex1_pool_data.loan_balance.clone(),
-                out_amount_dex1.clone(),
-                price_dex1.clone(),
-            ),
-        ])
-        .with(tabled::Style::rounded())
-        .to_string();
+                pool_data.address.to_string(),
+                pool_data,
+                quote,
+            ));
+        }
+
+        let table = Table::new(rows)
+            .with(tabled::Style::rounded())
+            .to_string();
 
         println!("\n{}\n", table);
     }
 
-
-    async fn get_pool_data(&self) -> Result<(PoolData, PoolData), ()> {
-        let dex0_pool_data = PoolData::from_pool_balance_out(
-            self.dex0
-                .get_pool_balance(self.asset_loan.clone(), self.asset_trade.clone())
-                .await,
-        );
-
-        let dex1_pool_data = PoolData::from_pool_balance_out(
-            self.dex1
-                .get_pool_balance(self.asset_loan.clone(), self.asset_trade.clone())
-                .await,
+    async fn get_route_pool_data(&self, route: &Route) -> Result<(PoolData, PoolData), DexError> {
+        return futures::try_join!(
+            self.get_pool_data(&route.buy_dex, &route.buy_quote.pool),
+            self.get_pool_data(&route.sell_dex, &route.sell_quote.pool)
         );
+    }
 
-        return Ok((dex0_pool_data, dex1_pool_data));
+    /// get the balances of the trade and loan assets held by `pool` of `dex`
+    async fn get_pool_data(&self, dex: &Arc<dyn Dex>, pool: &Pool) -> Result<PoolData, DexError> {
+        let (trade_balance, loan_balance) = dex
+            .get_pool_liquidity(pool, &self.asset_trade, &self.asset_loan)
+            .await?;
+        return Ok(PoolData {
+            trade_balance: Amount::new(trade_balance, self.asset_trade.decimals()),
+            loan_balance: Amount::new(loan_balance, self.asset_loan.decimals()),
+            address: pool.address,
+        });
     }
 
     #[inline]
@@ -283,41 +747,140 @@ impl Watcher {
         return (self.asset_trade.symbol(), self.asset_loan.symbol());
     }
 
+    /// roi of trading `in_amount` to `out_amount` paying `gas_cost`, all amounts of
+    /// the same asset, must be greater than `fees` in hundredths of a bip, only the
+    /// fees not already taken from `out_amount` are passed(e.g the flash loan one),
+    /// quoted outputs are net of the pools fees
     #[inline]
-    fn get_dexs_names(&self) -> (String, String) {
-        return (self.dex0.name(), self.dex1.name());
+    pub fn calc_roi(in_amount: &Amount, out_amount: &Amount, gas_cost: &Amount, fees: &u32) -> bool {
+        let Some(profit) = out_amount
+            .raw
+            .checked_sub(in_amount.raw)
+            .and_then(|profit| profit.checked_sub(gas_cost.raw)) else {
+            return false
+        };
+        // profit / in_amount > fees / FEE_DENOMINATOR, cross multiplied on 512 bits
+        return profit.full_mul(U256::from(FEE_DENOMINATOR)) > in_amount.raw.full_mul(U256::from(*fees));
     }
 
     #[inline]
-    pub fn get_loan_asset_sym(&self) -> String {
-        return self.asset_loan.symbol();
+    pub async fn call_arbitrageur(
+        &self,
+        route: &Route,
+        trade_size: &TradeSize,
+        flash_loan: &FlashLoan,
+        guard: &SlippageGuard,
+    ) -> Result<(TransactionReceipt, Status), CallerError> {
+        let receipt = self
+            .caller
+            .call(&self.asset_loan, &self.asset_trade, route, trade_size, flash_loan, guard)
+            .await?;
+        println!("arbitrage transaction {:?} mined", receipt.transaction_hash);
+        let status = self.caller.verify(&receipt, &self.asset_loan);
+        return Ok((receipt, status));
     }
 
-    #[inline]
-    pub fn get_trade_asset_sym(&self) -> String {
-        return self.asset_trade.symbol();
+}
+
+/// current unix time in seconds
+fn unix_time() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::Ierc20;
+    use crate::dexs::MockDex;
+
+    const BUY_POOL: Address = Address::repeat_byte(0x01);
+    const SELL_POOL: Address = Address::repeat_byte(0x02);
+
+    fn asset(address: Address, symbol: &str, decimals: u32) -> Asset {
+        let provider = Provider::<Http>::try_from("http://localhost:8545").unwrap();
+        return Asset {
+            contract: Ierc20::new(address, Arc::new(provider)),
+            address,
+            symbol: String::from(symbol),
+            decimals,
+            risks: None,
+            screening_error: None,
+        };
     }
 
-    #[inline]
-    pub fn calc_roi(in_amount: &f64,out_amount: &f64) -> bool {
-        let roi = ( out_amount - in_amount ) * 100.0 / in_amount ;
-        return roi > AAVE_FEE + UNISWAPV3_FEE + QUICKSWAPV3_FEE
+    /// two dexs with a pool each, 1 usdc buys 2 weth in both and 1 weth sells
+    /// for `sell_price` usdc on the second one and 0.45 on the first
+    async fn get_quotes(asset_trade: &Asset, asset_loan: &Asset, sell_price: u64) -> Vec<(Arc<dyn Dex>, Quote)> {
+        let buy_dex: Arc<dyn Dex> = Arc::new(
+            MockDex::new("buydex", 3000)
+                .with_rate(BUY_POOL, asset_loan, U256::exp10(18) * 2)
+                .with_rate(BUY_POOL, asset_trade, U256::from(450_000)),
+        );
+        let sell_dex: Arc<dyn Dex> = Arc::new(
+            MockDex::new("selldex", 3000)
+                .with_rate(SELL_POOL, asset_loan, U256::exp10(17) * 19)
+                .with_rate(SELL_POOL, asset_trade, U256::from(sell_price)),
+        );
 
+        let mut quotes = Vec::<(Arc<dyn Dex>, Quote)>::new();
+        for dex in [buy_dex, sell_dex] {
+            for side in [Side::Buy, Side::Sell] {
+                let dex_quotes = dex.check_assets_prices(asset_trade, asset_loan, side).await.unwrap();
+                quotes.extend(dex_quotes.into_iter().map(|quote| (dex.clone(), quote)));
+            }
+        }
+        return quotes;
     }
 
-    #[inline]
-    pub fn call_arbitrageur(&self,direction: Direction) -> Result<(),()> {
-        // let Ok(state) = arbitrageur(
-        //      self.asset_trade,
-        //      self.asset_loan,
-        //      amount,
-        //      direction,
-        //      fee
-        // ).call().await
-        //else {
-        //  return Err(())
-        // }
-        todo!()
+    #[tokio::test]
+    async fn select_route_picks_cheapest_buy_and_best_sell() {
+        let asset_trade = asset(Address::repeat_byte(0xaa), "WETH", 18);
+        let asset_loan = asset(Address::repeat_byte(0xbb), "USDC", 6);
+        let quotes = get_quotes(&asset_trade, &asset_loan, 550_000).await;
+
+        let route = Watcher::select_route(&quotes).unwrap();
+        assert_eq!(route.buy_dex.name(), "buydex");
+        assert_eq!(route.buy_quote.pool.address, BUY_POOL);
+        assert_eq!(route.buy_quote.out_amount.raw, U256::exp10(18) * 2);
+        assert_eq!(route.sell_dex.name(), "selldex");
+        assert_eq!(route.sell_quote.pool.address, SELL_POOL);
+        assert_eq!(route.sell_quote.out_amount.raw, U256::from(550_000));
+
+        // 1 usdc buys 2 weth that sell for 1.1 usdc
+        assert_eq!(Watcher::test_trade(&route, &asset_trade, &asset_loan).await, Ok(()));
     }
 
+    #[tokio::test]
+    async fn test_trade_rejects_sell_price_not_above_buy_price() {
+        let asset_trade = asset(Address::repeat_byte(0xaa), "WETH", 18);
+        let asset_loan = asset(Address::repeat_byte(0xbb), "USDC", 6);
+
+        // weth is bought at 0.5 usdc and sold at 0.5 usdc, or below it
+        for sell_price in [500_000, 490_000] {
+            let quotes = get_quotes(&asset_trade, &asset_loan, sell_price).await;
+            let route = Watcher::select_route(&quotes).unwrap();
+            assert_eq!(route.sell_quote.pool.address, SELL_POOL);
+            assert_eq!(
+                Watcher::test_trade(&route, &asset_trade, &asset_loan).await,
+                Err(String::from("sell price not above buy price"))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trade_rejects_unprofitable_round_trip() {
+        let asset_trade = asset(Address::repeat_byte(0xaa), "WETH", 18);
+        let asset_loan = asset(Address::repeat_byte(0xbb), "USDC", 6);
+        let quotes = get_quotes(&asset_trade, &asset_loan, 550_000).await;
+        let mut route = Watcher::select_route(&quotes).unwrap();
+
+        // the sell leg fills at 0.4 usdc per weth, below the quoted price
+        route.sell_dex = Arc::new(MockDex::new("selldex", 3000).with_rate(SELL_POOL, &asset_trade, U256::from(400_000)));
+        assert_eq!(
+            Watcher::test_trade(&route, &asset_trade, &asset_loan).await,
+            Err(String::from("not profitable buying 1 unit"))
+        );
+    }
 }